@@ -0,0 +1,146 @@
+use serde::Serialize;
+use span::{SourceMap, Span};
+
+use crate::{Diagnostic, LabelStyle};
+
+// Version of the JSON schema below. Bump it whenever a field is removed or
+// changes meaning; adding fields is backwards compatible.
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'diag> {
+    schema_version: u32,
+    file: Option<String>,
+    severity: &'static str,
+    code: Option<&'static str>,
+    message: &'diag str,
+    spans: Vec<JsonSpan<'diag>>,
+    notes: &'diag [String],
+    help: &'diag [String],
+    fixes: Vec<JsonFix<'diag>>,
+}
+
+#[derive(Serialize)]
+struct JsonSpan<'diag> {
+    #[serde(flatten)]
+    range: JsonRange,
+    is_primary: bool,
+    label: Option<&'diag str>,
+}
+
+#[derive(Serialize)]
+struct JsonFix<'diag> {
+    message: &'diag str,
+    #[serde(flatten)]
+    range: JsonRange,
+    replacement: &'diag str,
+}
+
+// Location of a span both as byte offsets and as 1-based lines/columns
+#[derive(Serialize)]
+struct JsonRange {
+    file: String,
+    byte_start: usize,
+    byte_end: usize,
+    line_start: usize,
+    col_start: usize,
+    line_end: usize,
+    col_end: usize,
+}
+
+impl JsonRange {
+    fn new(span: Span, source_map: &SourceMap) -> Self {
+        let start = source_map.start_location(span);
+        let end = source_map.end_location(span);
+
+        Self {
+            file: source_map.get(span.file).path.display().to_string(),
+            byte_start: span.start,
+            byte_end: span.end,
+            line_start: start.line_num,
+            col_start: start.col_num,
+            line_end: end.line_num,
+            col_end: end.col_num,
+        }
+    }
+}
+
+/// Serialise `diagnostic` as a single line of JSON (without a trailing newline).
+pub fn to_json_line(diagnostic: &Diagnostic, source_map: &SourceMap) -> String {
+    let spans = diagnostic
+        .labels
+        .iter()
+        .map(|label| JsonSpan {
+            range: JsonRange::new(label.span, source_map),
+            is_primary: label.style == LabelStyle::Primary,
+            label: (!label.message.is_empty()).then_some(label.message.as_str()),
+        })
+        .collect::<Vec<JsonSpan>>();
+
+    let fixes = diagnostic
+        .suggestions
+        .iter()
+        .map(|suggestion| JsonFix {
+            message: &suggestion.message,
+            range: JsonRange::new(suggestion.span, source_map),
+            replacement: &suggestion.replacement,
+        })
+        .collect::<Vec<JsonFix>>();
+
+    let json_diagnostic = JsonDiagnostic {
+        schema_version: SCHEMA_VERSION,
+        file: diagnostic
+            .primary_span()
+            .map(|span| source_map.get(span.file).path.display().to_string()),
+        severity: diagnostic.severity.as_str(),
+        code: diagnostic.code,
+        message: &diagnostic.message,
+        spans,
+        notes: &diagnostic.notes,
+        help: &diagnostic.help,
+        fixes,
+    };
+
+    serde_json::to_string(&json_diagnostic).expect("Diagnostics always serialise to JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn schema_is_stable() {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(PathBuf::from("main.gem"), "x := 1\ny := x;\n".to_string());
+        let diagnostic = Diagnostic::error("expected `;`")
+            .with_code("E0203")
+            .with_primary(Span::new(file, 6, 6), "")
+            .with_secondary(Span::new(file, 0, 1), "in this binding")
+            .with_note("statements end with `;`")
+            .with_help("add a `;`")
+            .with_suggestion(Span::new(file, 6, 6), "insert a semicolon", ";");
+
+        assert_eq!(
+            to_json_line(&diagnostic, &source_map),
+            concat!(
+                r#"{"schema_version":1,"file":"main.gem","severity":"error","code":"E0203","#,
+                r#""message":"expected `;`","spans":["#,
+                r#"{"file":"main.gem","byte_start":6,"byte_end":6,"line_start":1,"col_start":7,"line_end":1,"col_end":7,"is_primary":true,"label":null},"#,
+                r#"{"file":"main.gem","byte_start":0,"byte_end":1,"line_start":1,"col_start":1,"line_end":1,"col_end":2,"is_primary":false,"label":"in this binding"}],"#,
+                r#""notes":["statements end with `;`"],"help":["add a `;`"],"fixes":["#,
+                r#"{"message":"insert a semicolon","file":"main.gem","byte_start":6,"byte_end":6,"line_start":1,"col_start":7,"line_end":1,"col_end":7,"replacement":";"}]}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn diagnostics_without_spans_have_no_file() {
+        let source_map = SourceMap::new();
+        assert_eq!(
+            to_json_line(&Diagnostic::warning("no modules found"), &source_map),
+            r#"{"schema_version":1,"file":null,"severity":"warning","code":null,"message":"no modules found","spans":[],"notes":[],"help":[],"fixes":[]}"#
+        );
+    }
+}
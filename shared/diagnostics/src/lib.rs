@@ -0,0 +1,249 @@
+mod json;
+mod render;
+
+pub use json::to_json_line;
+pub use render::Renderer;
+use span::Span;
+
+/// Error codes reported by the compiler, grouped by the phase that emits them.
+pub mod codes {
+    // Preprocessor
+    pub const INVALID_CHARACTER: &str = "E0001";
+
+    // Scanner
+    pub const MALFORMED_NUM_LITERAL: &str = "E0100";
+    pub const MALFORMED_TYPE_QUALIFIER: &str = "E0101";
+    pub const UNKNOWN_TOKEN: &str = "E0102";
+    pub const INVALID_NUM_SUFFIX: &str = "E0103";
+    pub const UNTERMINATED_STRING: &str = "E0104";
+    pub const INVALID_ESCAPE: &str = "E0105";
+    pub const UNTERMINATED_CHAR: &str = "E0106";
+    pub const INVALID_CHAR_LITERAL: &str = "E0107";
+
+    // Parser
+    pub const UNEXPECTED_TOKEN: &str = "E0200";
+    pub const UNEXPECTED_EOF: &str = "E0201";
+    pub const MISSING_SEMICOLON: &str = "E0203";
+    pub const CHAINED_COMPARISON: &str = "E0204";
+    pub const INVALID_ASSIGN_TARGET: &str = "E0205";
+
+    // Typer
+    pub const NOT_A_TYPE: &str = "E0300";
+    pub const UNRESOLVED_TYPE: &str = "E0301";
+    pub const INVALID_LITERAL_SUFFIX: &str = "E0302";
+    pub const MISMATCHED_TYPES: &str = "E0303";
+    pub const ASSIGN_TO_IMMUTABLE: &str = "E0304";
+    pub const BREAK_OUTSIDE_LOOP: &str = "E0305";
+    pub const BREAK_WITH_VALUE: &str = "E0306";
+    pub const INVALID_OPERAND: &str = "E0307";
+    pub const NOT_CALLABLE: &str = "E0308";
+    pub const ARG_COUNT_MISMATCH: &str = "E0309";
+    pub const UNKNOWN_FIELD: &str = "E0310";
+    pub const NOT_A_VALUE: &str = "E0311";
+    pub const NOT_INDEXABLE: &str = "E0312";
+    pub const LITERAL_OUT_OF_RANGE: &str = "E0313";
+    pub const INVALID_CAST: &str = "E0314";
+
+    // Name resolution
+    pub const UNRESOLVED_IMPORT: &str = "E0400";
+    pub const IMPORT_CYCLE: &str = "E0401";
+    pub const DUPLICATE_DEFINITION: &str = "E0402";
+    pub const AMBIGUOUS_NAME: &str = "E0403";
+    pub const PRIVATE_ITEM: &str = "E0404";
+    pub const UNDECLARED_LABEL: &str = "E0405";
+    pub const UNRESOLVED_NAME: &str = "E0406";
+
+    // Name resolution warnings
+    pub const SHADOWED_NAME: &str = "W0400";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelStyle {
+    // Where the problem is
+    Primary,
+
+    // Related code that helps explain the problem
+    Secondary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub style: LabelStyle,
+    pub span: Span,
+    pub message: String,
+}
+
+/// A machine applicable fix: replace the text under `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// A problem found in a module, reported to the user instead of aborting the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+/// CTOR
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+}
+
+impl Diagnostic {
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            style: LabelStyle::Primary,
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            style: LabelStyle::Secondary,
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        span: Span,
+        message: impl Into<String>,
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Span of the first primary label, if any.
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .map(|label| label.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use span::FileId;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        // Read the codes back from this file so new ones are checked without listing them here
+        let mut seen: HashMap<&str, &str> = HashMap::new();
+        for line in include_str!("lib.rs").lines() {
+            let Some(decl) = line.trim().strip_prefix("pub const ") else {
+                continue;
+            };
+            let (name, value) = decl.split_once(": &str = ").expect("codes are `&str`s");
+            let code = value.trim_end_matches(';').trim_matches('"');
+
+            assert!(
+                code.len() == 5 && (code.starts_with('E') || code.starts_with('W')),
+                "`{name}` has malformed code `{code}`"
+            );
+            if let Some(other) = seen.insert(code, name) {
+                panic!("`{name}` and `{other}` share code `{code}`");
+            }
+        }
+        assert!(seen.contains_key(codes::UNEXPECTED_TOKEN));
+    }
+
+    #[test]
+    fn builder_collects_labels_in_order() {
+        let primary = Span::new(FileId(0), 4, 8);
+        let secondary = Span::new(FileId(0), 0, 2);
+        let diagnostic = Diagnostic::error("oops")
+            .with_code(codes::UNRESOLVED_TYPE)
+            .with_secondary(secondary, "because of this")
+            .with_primary(primary, "here")
+            .with_note("a note")
+            .with_help("some help");
+
+        assert!(diagnostic.is_error());
+        assert_eq!(diagnostic.code, Some("E0301"));
+        assert_eq!(diagnostic.primary_span(), Some(primary));
+        assert_eq!(diagnostic.labels[0].style, LabelStyle::Secondary);
+        assert_eq!(diagnostic.notes, ["a note"]);
+        assert_eq!(diagnostic.help, ["some help"]);
+
+        let warning = Diagnostic::warning("hmm");
+        assert!(!warning.is_error());
+        assert_eq!(warning.primary_span(), None);
+    }
+}
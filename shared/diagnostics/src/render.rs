@@ -0,0 +1,358 @@
+use std::fmt::Write;
+
+use span::{FileId, SourceMap, TAB_WIDTH, display_width};
+
+use crate::{Diagnostic, Label, LabelStyle, Severity};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Renders diagnostics for a terminal, quoting the offending source lines
+/// and underlining labeled spans with carets.
+pub struct Renderer<'renderer> {
+    source_map: &'renderer SourceMap,
+    color: bool,
+}
+
+/// CTOR
+impl<'renderer> Renderer<'renderer> {
+    pub fn new(source_map: &'renderer SourceMap, color: bool) -> Self {
+        Self { source_map, color }
+    }
+}
+
+// A single underline drawn beneath a source line
+struct Annotation<'label> {
+    line_num: usize,
+    col: usize,
+    width: usize,
+    style: LabelStyle,
+    message: Option<&'label str>,
+}
+
+impl<'renderer> Renderer<'renderer> {
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut out = String::new();
+        let severity_color = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+
+        // Header (e.g. `error[E0200]: expected `{`, found `->``)
+        let header = match diagnostic.code {
+            Some(code) => format!("{}[{code}]", diagnostic.severity.as_str()),
+            None => diagnostic.severity.as_str().to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{}{}",
+            self.paint(severity_color, &header),
+            self.paint(BOLD, &format!(": {}", diagnostic.message))
+        );
+
+        // Gutter must fit the largest line number we are going to print
+        let max_line_num = diagnostic
+            .labels
+            .iter()
+            .map(|label| self.source_map.end_location(label.span).line_num)
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(max_line_num.to_string().len());
+
+        // Primary labels first so the `-->` location points at the actual problem
+        let mut labels = diagnostic.labels.iter().collect::<Vec<&Label>>();
+        labels.sort_by_key(|label| label.style != LabelStyle::Primary);
+
+        let mut files: Vec<FileId> = Vec::new();
+        for label in &labels {
+            if !files.contains(&label.span.file) {
+                files.push(label.span.file);
+            }
+        }
+
+        for file in files {
+            let file_labels = labels
+                .iter()
+                .filter(|label| label.span.file == file)
+                .copied()
+                .collect::<Vec<&Label>>();
+            self.render_file(&mut out, &gutter, severity_color, &file_labels);
+        }
+
+        let has_footer = !diagnostic.notes.is_empty()
+            || !diagnostic.help.is_empty()
+            || !diagnostic.suggestions.is_empty();
+        if has_footer && !diagnostic.labels.is_empty() {
+            let _ = writeln!(out, "{gutter} {}", self.paint(BLUE, "|"));
+        }
+
+        for note in &diagnostic.notes {
+            let _ = writeln!(out, "{gutter} {} note: {note}", self.paint(BLUE, "="));
+        }
+
+        for help in &diagnostic.help {
+            let _ = writeln!(out, "{gutter} {} help: {help}", self.paint(BLUE, "="));
+        }
+
+        for suggestion in &diagnostic.suggestions {
+            let help = match suggestion.replacement.is_empty() {
+                true => suggestion.message.clone(),
+                false => format!("{}: `{}`", suggestion.message, suggestion.replacement),
+            };
+            let _ = writeln!(out, "{gutter} {} help: {help}", self.paint(BLUE, "="));
+        }
+
+        out
+    }
+
+    fn render_file(&self, out: &mut String, gutter: &str, severity_color: &str, labels: &[&Label]) {
+        let source_file = self.source_map.get(labels[0].span.file);
+        let location = source_file.location(labels[0].span.start);
+
+        let _ = writeln!(
+            out,
+            "{gutter}{} {}:{}:{}",
+            self.paint(BLUE, "-->"),
+            source_file.path.display(),
+            location.line_num,
+            location.col_num
+        );
+        let _ = writeln!(out, "{gutter} {}", self.paint(BLUE, "|"));
+
+        // Split every label into one underline per line it touches. The message
+        // is printed beneath the last line of the span.
+        let mut annotations: Vec<Annotation> = Vec::new();
+        for label in labels {
+            let span_lines = source_file.span_lines(label.span);
+            let last_line_num = span_lines.last().map(|line| line.line_num);
+
+            for span_line in span_lines {
+                let highlighted = &span_line.text[span_line.highlight.clone()];
+                annotations.push(Annotation {
+                    line_num: span_line.line_num,
+                    col: display_width(&span_line.text[..span_line.highlight.start]),
+                    width: display_width(highlighted).max(1),
+                    style: label.style,
+                    message: (Some(span_line.line_num) == last_line_num
+                        && !label.message.is_empty())
+                    .then_some(label.message.as_str()),
+                });
+            }
+        }
+
+        // Underlines sharing a line are drawn left to right
+        annotations.sort_by_key(|annotation| (annotation.line_num, annotation.col));
+
+        let mut line_nums = annotations
+            .iter()
+            .map(|annotation| annotation.line_num)
+            .collect::<Vec<usize>>();
+        line_nums.sort();
+        line_nums.dedup();
+
+        let mut prev_line_num: Option<usize> = None;
+        for line_num in line_nums {
+            if prev_line_num.is_some_and(|prev| line_num > prev + 1) {
+                let _ = writeln!(out, "{}", self.paint(BLUE, "..."));
+            }
+            prev_line_num = Some(line_num);
+
+            let _ = writeln!(
+                out,
+                "{} {}",
+                self.paint(BLUE, &format!("{line_num:>width$} |", width = gutter.len())),
+                expand_tabs(source_file.line_text(line_num)).trim_end()
+            );
+
+            for annotation in annotations.iter().filter(|a| a.line_num == line_num) {
+                let (mark, color) = match annotation.style {
+                    LabelStyle::Primary => ('^', severity_color),
+                    LabelStyle::Secondary => ('-', BLUE),
+                };
+
+                let mut underline = mark.to_string().repeat(annotation.width);
+                if let Some(message) = annotation.message {
+                    underline.push(' ');
+                    underline.push_str(message);
+                }
+
+                let _ = writeln!(
+                    out,
+                    "{gutter} {} {}{}",
+                    self.paint(BLUE, "|"),
+                    " ".repeat(annotation.col),
+                    self.paint(color, &underline)
+                );
+            }
+        }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        match self.color {
+            true => format!("{style}{text}{RESET}"),
+            false => text.to_string(),
+        }
+    }
+}
+
+fn expand_tabs(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for chr in text.chars() {
+        match chr {
+            '\t' => {
+                let width = display_width(&expanded);
+                expanded.push_str(&" ".repeat(TAB_WIDTH - width % TAB_WIDTH));
+            }
+            _ => expanded.push(chr),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use span::Span;
+
+    use super::*;
+
+    fn render(src: &str, diagnostic: impl FnOnce(FileId) -> Diagnostic) -> String {
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(PathBuf::from("main.gem"), src.to_string());
+        Renderer::new(&source_map, false).render(&diagnostic(file))
+    }
+
+    #[test]
+    fn single_line_span() {
+        let out = render("x := 1 + true;\n", |file| {
+            Diagnostic::error("mismatched types")
+                .with_code("E0303")
+                .with_primary(Span::new(file, 9, 13), "expected an integer, found `bool`")
+                .with_secondary(Span::new(file, 5, 6), "integer")
+        });
+        assert_eq!(
+            out,
+            "\
+error[E0303]: mismatched types
+ --> main.gem:1:10
+  |
+1 | x := 1 + true;
+  |      - integer
+  |          ^^^^ expected an integer, found `bool`
+"
+        );
+    }
+
+    #[test]
+    fn multi_line_span() {
+        let src = "f :: proc () {\n    x := 1;\n}\n";
+        let out = render(src, |file| {
+            Diagnostic::error("unclosed body")
+                .with_primary(Span::new(file, 13, src.len() - 1), "here")
+        });
+        assert_eq!(
+            out,
+            "\
+error: unclosed body
+ --> main.gem:1:14
+  |
+1 | f :: proc () {
+  |              ^
+2 |     x := 1;
+  | ^^^^^^^^^^^
+3 | }
+  | ^ here
+"
+        );
+    }
+
+    #[test]
+    fn distant_lines_are_elided() {
+        let src = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let out = render(src, |file| {
+            Diagnostic::error("duplicate definition")
+                .with_primary(Span::new(file, 18, 19), "redefined")
+                .with_secondary(Span::new(file, 0, 1), "first defined")
+        });
+        assert_eq!(
+            out,
+            "\
+error: duplicate definition
+  --> main.gem:10:1
+   |
+ 1 | a
+   | - first defined
+...
+10 | j
+   | ^ redefined
+"
+        );
+    }
+
+    #[test]
+    fn tabs_are_expanded_to_display_columns() {
+        let out = render("\tx :=\t1;\n", |file| {
+            Diagnostic::error("oops").with_primary(Span::new(file, 6, 7), "here")
+        });
+        assert_eq!(
+            out,
+            "\
+error: oops
+ --> main.gem:1:7
+  |
+1 |     x :=    1;
+  |             ^ here
+"
+        );
+    }
+
+    #[test]
+    fn notes_help_and_suggestions_follow_the_source() {
+        let out = render("x := 1\n", |file| {
+            Diagnostic::warning("missing `;`")
+                .with_primary(Span::new(file, 6, 6), "")
+                .with_note("statements end with `;`")
+                .with_help("add a `;`")
+                .with_suggestion(Span::new(file, 6, 6), "insert", ";")
+        });
+        assert_eq!(
+            out,
+            "\
+warning: missing `;`
+ --> main.gem:1:7
+  |
+1 | x := 1
+  |       ^
+  |
+  = note: statements end with `;`
+  = help: add a `;`
+  = help: insert: `;`
+"
+        );
+    }
+
+    #[test]
+    fn diagnostics_without_labels_only_print_the_footer() {
+        let out = render("", |_| {
+            Diagnostic::error("no modules found").with_note("pass a root directory")
+        });
+        assert_eq!(
+            out,
+            "error: no modules found\n  = note: pass a root directory\n"
+        );
+    }
+
+    #[test]
+    fn color_wraps_each_part() {
+        let mut source_map = SourceMap::new();
+        source_map.add_file(PathBuf::from("main.gem"), String::new());
+        let out = Renderer::new(&source_map, true).render(&Diagnostic::error("oops"));
+        assert_eq!(out, format!("{RED}error{RESET}{BOLD}: oops{RESET}\n"));
+    }
+}
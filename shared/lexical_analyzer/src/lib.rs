@@ -1,5 +1,6 @@
-#![feature(let_chains)]
+use std::{fmt::Display, path::PathBuf};
 
+use diagnostics::{Diagnostic, codes};
 use module_manager::{Module, ModuleManager};
 use span::Span;
 use thiserror::Error;
@@ -9,6 +10,9 @@ pub struct Token {
     pub content: String,
     pub kind: Option<TokenKind>,
     pub span: Span,
+
+    // Value of literal tokens, set by the scanner
+    pub literal: Option<Literal>,
 }
 
 impl Token {
@@ -16,39 +20,60 @@ impl Token {
         Self {
             content: String::new(),
             kind: None,
-            span: Span::new(),
+            span: Span::default(),
+            literal: None,
         }
     }
 
-    pub fn assert_kind(&self, kind: TokenKind) {
+    /// Parts of a well-formed number literal.
+    pub fn num_literal(&self) -> Option<&NumLiteral> {
+        match &self.literal {
+            Some(Literal::Num(literal)) => Some(literal),
+            _ => None,
+        }
+    }
+
+    /// Human readable description used in diagnostics (e.g. "identifier `foo`").
+    pub fn describe(&self) -> String {
         match self.kind {
-            Some(token_kind) => {
-                if token_kind == kind {
-                    return;
-                } else {
-                    panic!("Expected token kind: {kind:?}, but got: {token_kind:?}")
-                }
+            Some(
+                kind @ (TokenKind::IntLiteral
+                | TokenKind::FloatLiteral
+                | TokenKind::StringLiteral
+                | TokenKind::CharLiteral
+                | TokenKind::BoolLiteral
+                | TokenKind::Identifier
+                | TokenKind::Label),
+            ) => {
+                format!("{kind} `{}`", self.content)
             }
-            _ => panic!("Expected token kind: {kind:?}, but got: None"),
+            Some(kind) => kind.to_string(),
+            None => format!("unrecognised token `{}`", self.content),
         }
     }
 
-    pub fn assert_allowed_kinds(self, kinds: &[TokenKind]) -> Self {
-        let mut match_found = false;
-        let self_kind = self
-            .kind
-            .expect("Token kind should not be None. Possible error in scanner.");
+    pub fn expect_kind(self, kind: TokenKind) -> Result<Self, Box<Diagnostic>> {
+        self.expect_allowed_kinds(&[kind])
+    }
 
-        for kind in kinds {
-            if self_kind == *kind {
-                match_found = true;
-            }
+    pub fn expect_allowed_kinds(self, kinds: &[TokenKind]) -> Result<Self, Box<Diagnostic>> {
+        if let Some(self_kind) = self.kind
+            && kinds.contains(&self_kind)
+        {
+            return Ok(self);
         }
 
-        match match_found {
-            true => self,
-            false => panic!("Expected token kinds: {kinds:?}, but got: {self_kind:?}"),
-        }
+        let expected = kinds
+            .iter()
+            .map(|kind| kind.to_string())
+            .collect::<Vec<String>>()
+            .join(" or ");
+
+        Err(Box::new(
+            Diagnostic::error(format!("expected {expected}, found {}", self.describe()))
+                .with_code(codes::UNEXPECTED_TOKEN)
+                .with_primary(self.span, format!("expected {expected}")),
+        ))
     }
 }
 
@@ -57,6 +82,9 @@ pub struct ModuleTokenStream<'scanner> {
     pub module: &'scanner Module,
     pub tokens: Vec<Token>,
     pub cursor: usize,
+
+    // Errors recovered from while consuming this stream
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl ModuleTokenStream<'_> {
@@ -69,22 +97,108 @@ impl ModuleTokenStream<'_> {
     pub fn peek_token(&self) -> Option<Token> {
         self.tokens.get(self.cursor).cloned()
     }
+
+    /// Peek `n` tokens past the next one (`peek_nth_kind(0)` is the next token).
+    pub fn peek_nth_kind(&self, n: usize) -> Option<TokenKind> {
+        self.tokens
+            .get(self.cursor + n)
+            .and_then(|token| token.kind)
+    }
+
+    pub fn next_is(&self, kind: TokenKind) -> bool {
+        self.peek_nth_kind(0) == Some(kind)
+    }
+
+    /// Record an error and keep going. The caller is responsible for recovery.
+    pub fn report(&mut self, diagnostic: Diagnostic) {
+        // Errors pointing at an `Error` token would only repeat what the scanner reported.
+        // So would running out of tokens after an unterminated string.
+        let at_error_token = diagnostic.primary_span().is_some_and(|span| {
+            self.tokens.iter().any(|token| {
+                token.kind == Some(TokenKind::Error)
+                    && (token.span == span
+                        || token.span.end == span.start && span == self.eof_span())
+            })
+        });
+
+        // A second error at the same token is a knock-on effect of the first
+        let repeats_previous = diagnostic.primary_span().is_some()
+            && self.diagnostics.last().and_then(Diagnostic::primary_span)
+                == diagnostic.primary_span();
+
+        if !at_error_token && !repeats_previous {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Error recovery: skip tokens until `stop` holds for the stream outside of
+    /// any bracket pair opened while skipping. Never skips past a closing bracket
+    /// that was opened before recovery started, nor past the end of the module.
+    /// Brackets opened while skipping but never closed (e.g. `[1; }`) do not
+    /// hide the closing bracket of the enclosing construct.
+    pub fn skip_until(&mut self, stop: impl Fn(&Self) -> bool) {
+        use PunctuationKind::*;
+
+        let mut opened: Vec<PunctuationKind> = Vec::new();
+        while let Some(kind) = self.peek_nth_kind(0) {
+            if opened.is_empty() && stop(self) {
+                return;
+            }
+
+            match kind {
+                TokenKind::Punctuation(open @ (OpenParen | OpenBracket | OpenBrace)) => {
+                    opened.push(open)
+                }
+                TokenKind::Punctuation(close @ (CloseParen | CloseBracket | CloseBrace)) => {
+                    let pair = opened.iter().rposition(|&open| {
+                        matches!(
+                            (open, close),
+                            (OpenParen, CloseParen)
+                                | (OpenBracket, CloseBracket)
+                                | (OpenBrace, CloseBrace)
+                        )
+                    });
+                    match pair {
+                        Some(idx) => opened.truncate(idx),
+                        None => return,
+                    }
+                }
+                _ => (),
+            }
+
+            self.cursor += 1;
+        }
+    }
+
+    /// Like `get_token`, but running out of tokens is reported as an error.
+    pub fn expect_token(&mut self) -> Result<Token, Box<Diagnostic>> {
+        self.get_token().ok_or_else(|| Box::new(self.eof_error()))
+    }
+
+    pub fn eof_error(&self) -> Diagnostic {
+        Diagnostic::error(format!(
+            "unexpected end of module `{}`",
+            self.module.path.display()
+        ))
+        .with_code(codes::UNEXPECTED_EOF)
+        .with_primary(self.eof_span(), "module ends here")
+    }
+
+    /// Empty span sitting at the very end of the module.
+    pub fn eof_span(&self) -> Span {
+        Span::new(self.module.id, self.module.src.len(), self.module.src.len())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Scanner<'scanner> {
     module_manager: &'scanner ModuleManager,
-    token_stream: Vec<ModuleTokenStream<'scanner>>,
 }
 
 // CTOR
 impl<'scanner> Scanner<'scanner> {
     pub fn new(module_manager: &'scanner ModuleManager) -> Self {
-        let token_stream = Vec::new();
-        Self {
-            module_manager,
-            token_stream,
-        }
+        Self { module_manager }
     }
 }
 
@@ -94,10 +208,22 @@ pub enum ReservedKind {
     Enum,
     And,
     Or,
+    Not,
     If,
     Else,
     Main,
     Proc,
+    Import,
+    Pub,
+    Return,
+    Mut,
+    While,
+    For,
+    In,
+    Loop,
+    Break,
+    Continue,
+    As,
     PrimTy(ScannerPrimKind),
 }
 
@@ -112,6 +238,10 @@ pub enum ScannerPrimKind {
     U16,
     U32,
     U64,
+    F32,
+    F64,
+    Char,
+    Str,
 }
 
 impl TryFrom<&str> for ReservedKind {
@@ -121,12 +251,24 @@ impl TryFrom<&str> for ReservedKind {
         match value {
             "and" => Ok(ReservedKind::And),
             "or" => Ok(ReservedKind::Or),
+            "not" => Ok(ReservedKind::Not),
             "if" => Ok(ReservedKind::If),
             "else" => Ok(ReservedKind::Else),
             "main" => Ok(ReservedKind::Main),
             "struct" => Ok(ReservedKind::Struct),
             "enum" => Ok(ReservedKind::Enum),
             "proc" => Ok(ReservedKind::Proc),
+            "import" => Ok(ReservedKind::Import),
+            "pub" => Ok(ReservedKind::Pub),
+            "return" => Ok(ReservedKind::Return),
+            "mut" => Ok(ReservedKind::Mut),
+            "while" => Ok(ReservedKind::While),
+            "for" => Ok(ReservedKind::For),
+            "in" => Ok(ReservedKind::In),
+            "loop" => Ok(ReservedKind::Loop),
+            "break" => Ok(ReservedKind::Break),
+            "continue" => Ok(ReservedKind::Continue),
+            "as" => Ok(ReservedKind::As),
             "bool" => Ok(ReservedKind::PrimTy(ScannerPrimKind::Bool)),
             "s8" => Ok(ReservedKind::PrimTy(ScannerPrimKind::S8)),
             "s16" => Ok(ReservedKind::PrimTy(ScannerPrimKind::S16)),
@@ -136,11 +278,425 @@ impl TryFrom<&str> for ReservedKind {
             "u16" => Ok(ReservedKind::PrimTy(ScannerPrimKind::U16)),
             "u32" => Ok(ReservedKind::PrimTy(ScannerPrimKind::U32)),
             "u64" => Ok(ReservedKind::PrimTy(ScannerPrimKind::U64)),
+            "f32" => Ok(ReservedKind::PrimTy(ScannerPrimKind::F32)),
+            "f64" => Ok(ReservedKind::PrimTy(ScannerPrimKind::F64)),
+            "char" => Ok(ReservedKind::PrimTy(ScannerPrimKind::Char)),
+            "str" => Ok(ReservedKind::PrimTy(ScannerPrimKind::Str)),
             _ => Err("Invalid reserved keyword"),
         }
     }
 }
 
+impl Display for ReservedKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keyword = match self {
+            ReservedKind::Struct => "struct",
+            ReservedKind::Enum => "enum",
+            ReservedKind::And => "and",
+            ReservedKind::Or => "or",
+            ReservedKind::Not => "not",
+            ReservedKind::If => "if",
+            ReservedKind::Else => "else",
+            ReservedKind::Main => "main",
+            ReservedKind::Proc => "proc",
+            ReservedKind::Import => "import",
+            ReservedKind::Pub => "pub",
+            ReservedKind::Return => "return",
+            ReservedKind::Mut => "mut",
+            ReservedKind::While => "while",
+            ReservedKind::For => "for",
+            ReservedKind::In => "in",
+            ReservedKind::Loop => "loop",
+            ReservedKind::Break => "break",
+            ReservedKind::Continue => "continue",
+            ReservedKind::As => "as",
+            ReservedKind::PrimTy(prim_kind) => match prim_kind {
+                ScannerPrimKind::Bool => "bool",
+                ScannerPrimKind::S8 => "s8",
+                ScannerPrimKind::S16 => "s16",
+                ScannerPrimKind::S32 => "s32",
+                ScannerPrimKind::S64 => "s64",
+                ScannerPrimKind::U8 => "u8",
+                ScannerPrimKind::U16 => "u16",
+                ScannerPrimKind::U32 => "u32",
+                ScannerPrimKind::U64 => "u64",
+                ScannerPrimKind::F32 => "f32",
+                ScannerPrimKind::F64 => "f64",
+                ScannerPrimKind::Char => "char",
+                ScannerPrimKind::Str => "str",
+            },
+        };
+
+        write!(f, "{keyword}")
+    }
+}
+
+/// Value of a literal token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Literal {
+    Num(NumLiteral),
+
+    // Text of a string literal with its escape sequences decoded
+    Str(String),
+    Char(char),
+    Bool(bool),
+}
+
+/// Number literal split into its parts, e.g. `0xff_u8` or `1.5e10`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumLiteral {
+    pub radix: Radix,
+
+    // Without the radix prefix, the suffix and `_` separators. Float digits
+    // keep their `.` and exponent (e.g. `1.5e-3`).
+    pub digits: String,
+    pub suffix: Option<NumSuffix>,
+}
+
+impl NumLiteral {
+    /// Value of an integer literal, `None` if it does not fit in a `u128`.
+    pub fn int_value(&self) -> Option<u128> {
+        u128::from_str_radix(&self.digits, self.radix.base()).ok()
+    }
+
+    /// Value of a float literal. Too large values are infinite.
+    pub fn float_value(&self) -> Option<f64> {
+        self.digits.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    // `0b`
+    Binary,
+
+    // `0o`
+    Octal,
+    Decimal,
+
+    // `0x`
+    Hexadecimal,
+}
+
+impl Radix {
+    pub fn base(self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+}
+
+/// Type written after the digits of a number literal (e.g. the `u8` of `2u8`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumSuffix {
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    F64,
+}
+
+impl TryFrom<&str> for NumSuffix {
+    type Error = &'static str;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "u8" => Ok(NumSuffix::U8),
+            "u16" => Ok(NumSuffix::U16),
+            "u32" => Ok(NumSuffix::U32),
+            "u64" => Ok(NumSuffix::U64),
+            "s8" => Ok(NumSuffix::S8),
+            "s16" => Ok(NumSuffix::S16),
+            "s32" => Ok(NumSuffix::S32),
+            "s64" => Ok(NumSuffix::S64),
+            "f32" => Ok(NumSuffix::F32),
+            "f64" => Ok(NumSuffix::F64),
+            _ => Err("Invalid number literal suffix"),
+        }
+    }
+}
+
+// Split a number literal into its parts and tell whether it is an integer or
+// a float literal. `1e3` and `1f32` are floats, like `1.0`.
+fn split_num_literal(content: &str) -> Result<(TokenKind, NumLiteral), ScannerErrorKind> {
+    let (radix, rest) = match content.get(..2) {
+        Some("0b") => (Radix::Binary, &content[2..]),
+        Some("0o") => (Radix::Octal, &content[2..]),
+        Some("0x") => (Radix::Hexadecimal, &content[2..]),
+        _ => (Radix::Decimal, content),
+    };
+
+    // `_` separates digits, so two in a row are a typo
+    if rest.contains("__") {
+        return Err(ScannerErrorKind::MalformedNumLit);
+    }
+
+    // Digits end at the first character that cannot be part of them
+    let mut is_float = false;
+    let mut has_exponent = false;
+    let mut digits = String::new();
+    let mut suffix_start = rest.len();
+    let mut chars = rest.char_indices().peekable();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '_' => continue,
+            _ if ch.is_digit(radix.base()) => digits.push(ch),
+            '.' if radix == Radix::Decimal && !is_float => {
+                is_float = true;
+                digits.push(ch);
+            }
+            'e' | 'E' if radix == Radix::Decimal && !has_exponent => {
+                is_float = true;
+                has_exponent = true;
+                digits.push('e');
+                if let Some(&(_, sign @ ('+' | '-'))) = chars.peek() {
+                    digits.push(sign);
+                    chars.next();
+                }
+
+                // The exponent needs digits of its own
+                if !chars.peek().is_some_and(|&(_, c)| c.is_ascii_digit()) {
+                    return Err(ScannerErrorKind::MalformedNumLit);
+                }
+            }
+            _ => {
+                suffix_start = idx;
+                break;
+            }
+        }
+    }
+
+    if !digits.starts_with(|c: char| c.is_digit(radix.base())) {
+        return Err(ScannerErrorKind::MalformedNumLit);
+    }
+
+    let suffix = match &rest[suffix_start..] {
+        "" => None,
+        suffix if suffix.starts_with(|c: char| c.is_alphabetic()) => {
+            Some(NumSuffix::try_from(suffix).map_err(|_| ScannerErrorKind::InvalidNumSuffix)?)
+        }
+
+        // e.g. the `2` of `0b12` or the second `.` of `1.2.3`
+        _ => return Err(ScannerErrorKind::MalformedNumLit),
+    };
+
+    if matches!(suffix, Some(NumSuffix::F32 | NumSuffix::F64)) {
+        is_float = true;
+    }
+
+    // Floats are only written in decimal
+    if is_float && radix != Radix::Decimal {
+        return Err(ScannerErrorKind::MalformedNumLit);
+    }
+
+    let kind = match is_float {
+        true => TokenKind::FloatLiteral,
+        false => TokenKind::IntLiteral,
+    };
+
+    Ok((
+        kind,
+        NumLiteral {
+            radix,
+            digits,
+            suffix,
+        },
+    ))
+}
+
+/// String literal read by `scan_string`.
+#[derive(Debug)]
+struct ScannedString {
+    text: String,
+
+    // Chars making up the literal from its opening quote, closing quote and `#`s included
+    len: usize,
+
+    // Whether the closing quote was found before the end of the module
+    terminated: bool,
+
+    // Malformed escape sequences, as ranges of char indices
+    bad_escapes: Vec<(usize, usize)>,
+}
+
+// Number of `#` opening the raw string `chars` starts with, if it is one (e.g.
+// 1 for the `#"` of `r#"text"#`)
+fn raw_string_hashes(chars: &[(usize, char)]) -> Option<usize> {
+    let hashes = chars.iter().take_while(|&&(_, c)| c == '#').count();
+    (chars.get(hashes).map(|&(_, c)| c) == Some('"')).then_some(hashes)
+}
+
+// Read the string literal opened by the quote `chars[0]`. Raw strings have
+// no escapes and end at a quote followed by as many `#` as opened them.
+fn scan_string(chars: &[(usize, char)], raw_hashes: Option<usize>) -> ScannedString {
+    let mut text = String::new();
+    let mut bad_escapes: Vec<(usize, usize)> = Vec::new();
+
+    let mut idx = 1;
+    while let Some(&(_, ch)) = chars.get(idx) {
+        match (ch, raw_hashes) {
+            ('"', None) => {
+                return ScannedString {
+                    text,
+                    len: idx + 1,
+                    terminated: true,
+                    bad_escapes,
+                };
+            }
+            ('"', Some(hashes))
+                if chars
+                    .get(idx + 1..idx + 1 + hashes)
+                    .is_some_and(|closing| closing.iter().all(|&(_, c)| c == '#')) =>
+            {
+                return ScannedString {
+                    text,
+                    len: idx + 1 + hashes,
+                    terminated: true,
+                    bad_escapes,
+                };
+            }
+            ('\\', None) => {
+                let (escaped, len) = scan_escape(&chars[idx + 1..]);
+                match escaped {
+                    Some(escaped) => text.push(escaped),
+                    None => bad_escapes.push((idx, idx + 1 + len)),
+                }
+                idx += 1 + len;
+                continue;
+            }
+            _ => text.push(ch),
+        }
+
+        idx += 1;
+    }
+
+    ScannedString {
+        text,
+        len: chars.len(),
+        terminated: false,
+        bad_escapes,
+    }
+}
+
+/// Char literal read by `scan_char`.
+#[derive(Debug)]
+struct ScannedChar {
+    // `None` if the literal is empty or holds more than one char
+    value: Option<char>,
+
+    // Chars making up the literal from its opening quote, closing quote included
+    len: usize,
+
+    // Whether the closing quote was found on the same line
+    terminated: bool,
+
+    // Malformed escape sequences, as ranges of char indices
+    bad_escapes: Vec<(usize, usize)>,
+}
+
+// Read the char literal opened by the quote `chars[0]`. It takes the same
+// escapes as strings, but may not span lines.
+fn scan_char(chars: &[(usize, char)]) -> ScannedChar {
+    let mut decoded: Vec<char> = Vec::new();
+    let mut bad_escapes: Vec<(usize, usize)> = Vec::new();
+
+    let mut idx = 1;
+    while let Some(&(_, ch)) = chars.get(idx) {
+        match ch {
+            '\'' => {
+                return ScannedChar {
+                    value: match decoded[..] {
+                        [value] => Some(value),
+                        _ => None,
+                    },
+                    len: idx + 1,
+                    terminated: true,
+                    bad_escapes,
+                };
+            }
+            '\n' => break,
+            '\\' => {
+                let (escaped, len) = scan_escape(&chars[idx + 1..]);
+                match escaped {
+                    Some(escaped) => decoded.push(escaped),
+                    None => bad_escapes.push((idx, idx + 1 + len)),
+                }
+                idx += 1 + len;
+                continue;
+            }
+            _ => decoded.push(ch),
+        }
+
+        idx += 1;
+    }
+
+    ScannedChar {
+        value: None,
+        len: idx,
+        terminated: false,
+        bad_escapes,
+    }
+}
+
+// Decode the escape sequence following a `\`. Returns the escaped char, `None`
+// if the sequence is malformed, and the number of chars it spans after the `\`.
+fn scan_escape(chars: &[(usize, char)]) -> (Option<char>, usize) {
+    let Some(&(_, ch)) = chars.first() else {
+        return (None, 0);
+    };
+
+    let escaped = match ch {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+
+        // `\u{1F48E}`: up to 6 hex digits naming a Unicode scalar value
+        'u' => {
+            if chars.get(1).map(|&(_, c)| c) != Some('{') {
+                return (None, 1);
+            }
+
+            let digits: String = chars[2..]
+                .iter()
+                .map(|&(_, c)| c)
+                .take_while(|c| c.is_ascii_hexdigit())
+                .collect();
+            if chars.get(2 + digits.len()).map(|&(_, c)| c) != Some('}') {
+                return (None, 2 + digits.len());
+            }
+
+            let escaped = Some(digits.as_str())
+                .filter(|digits| (1..=6).contains(&digits.len()))
+                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                .and_then(char::from_u32);
+            return (escaped, 3 + digits.len());
+        }
+        _ => return (None, 1),
+    };
+
+    (Some(escaped), 1)
+}
+
+// Whether `content` is a decimal number literal waiting for the sign or
+// digits of its exponent (e.g. `1.5e`)
+fn ends_in_exponent(content: &str) -> bool {
+    content.starts_with(|c: char| c.is_ascii_digit())
+        && !matches!(content.get(..2), Some("0b" | "0o" | "0x"))
+        && content.ends_with(['e', 'E'])
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PunctuationKind {
     OpenParen,
@@ -171,6 +727,24 @@ impl From<char> for PunctuationKind {
     }
 }
 
+impl Display for PunctuationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let punctuation = match self {
+            PunctuationKind::OpenParen => '(',
+            PunctuationKind::CloseParen => ')',
+            PunctuationKind::OpenBracket => '[',
+            PunctuationKind::CloseBracket => ']',
+            PunctuationKind::OpenBrace => '{',
+            PunctuationKind::CloseBrace => '}',
+            PunctuationKind::Comma => ',',
+            PunctuationKind::Dot => '.',
+            PunctuationKind::Semicolon => ';',
+        };
+
+        write!(f, "{punctuation}")
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OperatorKind {
     TypeArrow,
@@ -178,58 +752,182 @@ pub enum OperatorKind {
     Sub,
     Mul,
     Div,
+    Mod,
     TypeQualifier,
+    InferAssign,
     Assign,
     AssignPlus,
     AssignSub,
     AssignMul,
     AssignDiv,
+    AssignMod,
+    AssignBitAnd,
+    AssignBitOr,
+    AssignBitXor,
+    AssignShl,
+    AssignShr,
     LT,
     LTE,
     GT,
+    GTE,
     Eq,
+    NotEq,
+    Not,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    Shl,
+    Shr,
+    Range,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum OperatorComplexity {
-    // Single char operator
-    Simple,
+impl Display for OperatorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operator = match self {
+            OperatorKind::TypeArrow => "->",
+            OperatorKind::Plus => "+",
+            OperatorKind::Sub => "-",
+            OperatorKind::Mul => "*",
+            OperatorKind::Div => "/",
+            OperatorKind::Mod => "%",
+            OperatorKind::TypeQualifier => "::",
+            OperatorKind::InferAssign => ":=",
+            OperatorKind::Assign => "=",
+            OperatorKind::AssignPlus => "+=",
+            OperatorKind::AssignSub => "-=",
+            OperatorKind::AssignMul => "*=",
+            OperatorKind::AssignDiv => "/=",
+            OperatorKind::AssignMod => "%=",
+            OperatorKind::AssignBitAnd => "&=",
+            OperatorKind::AssignBitOr => "|=",
+            OperatorKind::AssignBitXor => "^=",
+            OperatorKind::AssignShl => "<<=",
+            OperatorKind::AssignShr => ">>=",
+            OperatorKind::LT => "<",
+            OperatorKind::LTE => "<=",
+            OperatorKind::GT => ">",
+            OperatorKind::GTE => ">=",
+            OperatorKind::Eq => "==",
+            OperatorKind::NotEq => "!=",
+            OperatorKind::Not => "!",
+            OperatorKind::BitAnd => "&",
+            OperatorKind::BitOr => "|",
+            OperatorKind::BitXor => "^",
+            OperatorKind::BitNot => "~",
+            OperatorKind::Shl => "<<",
+            OperatorKind::Shr => ">>",
+            OperatorKind::Range => "..",
+        };
 
-    // Two char operator
-    Complex,
+        write!(f, "{operator}")
+    }
 }
 
-impl From<OperatorKind> for OperatorComplexity {
-    fn from(value: OperatorKind) -> Self {
-        use OperatorComplexity::*;
-        match value {
-            OperatorKind::TypeArrow => Simple,
-            OperatorKind::Plus => Simple,
-            OperatorKind::Sub => Simple,
-            OperatorKind::Mul => Simple,
-            OperatorKind::Div => Simple,
-            OperatorKind::TypeQualifier => Complex,
-            OperatorKind::Assign => Simple,
-            OperatorKind::AssignPlus => Complex,
-            OperatorKind::AssignSub => Complex,
-            OperatorKind::AssignMul => Complex,
-            OperatorKind::AssignDiv => Complex,
-            OperatorKind::LT => Simple,
-            OperatorKind::LTE => Complex,
-            OperatorKind::GT => Simple,
-            OperatorKind::Eq => Simple,
-        }
+// Length in chars of the label starting at the quote `chars[0]`, if it is one.
+// `'a'` is a char literal, `'a` a label.
+fn label_len(chars: &[(usize, char)]) -> Option<usize> {
+    if chars.first().map(|&(_, c)| c) != Some('\'') {
+        return None;
+    }
+
+    let name_len = chars[1..]
+        .iter()
+        .take_while(|&&(_, c)| c.is_alphanumeric() || c == '_')
+        .count();
+    let starts_name = chars
+        .get(1)
+        .is_some_and(|&(_, c)| c.is_alphabetic() || c == '_');
+    let closed = chars.get(1 + name_len).map(|&(_, c)| c) == Some('\'');
+    match starts_name && !closed {
+        true => Some(1 + name_len),
+        false => None,
     }
 }
 
+// Operators made of operator characters, longest first so that the scanner
+// takes the longest one starting at a character (e.g. `<<=` over `<<` and `<`).
+// `..` is scanned on its own as `.` also appears in number literals.
+const OPERATORS: &[(&str, OperatorKind)] = &[
+    ("<<=", OperatorKind::AssignShl),
+    (">>=", OperatorKind::AssignShr),
+    ("->", OperatorKind::TypeArrow),
+    ("::", OperatorKind::TypeQualifier),
+    (":=", OperatorKind::InferAssign),
+    ("==", OperatorKind::Eq),
+    ("!=", OperatorKind::NotEq),
+    ("<=", OperatorKind::LTE),
+    (">=", OperatorKind::GTE),
+    ("<<", OperatorKind::Shl),
+    (">>", OperatorKind::Shr),
+    ("+=", OperatorKind::AssignPlus),
+    ("-=", OperatorKind::AssignSub),
+    ("*=", OperatorKind::AssignMul),
+    ("/=", OperatorKind::AssignDiv),
+    ("%=", OperatorKind::AssignMod),
+    ("&=", OperatorKind::AssignBitAnd),
+    ("|=", OperatorKind::AssignBitOr),
+    ("^=", OperatorKind::AssignBitXor),
+    ("+", OperatorKind::Plus),
+    ("-", OperatorKind::Sub),
+    ("*", OperatorKind::Mul),
+    ("/", OperatorKind::Div),
+    ("%", OperatorKind::Mod),
+    ("=", OperatorKind::Assign),
+    ("<", OperatorKind::LT),
+    (">", OperatorKind::GT),
+    ("!", OperatorKind::Not),
+    ("&", OperatorKind::BitAnd),
+    ("|", OperatorKind::BitOr),
+    ("^", OperatorKind::BitXor),
+    ("~", OperatorKind::BitNot),
+];
+
+// Longest operator `src` starts with
+fn munch_operator(src: &str) -> Option<(&'static str, OperatorKind)> {
+    OPERATORS
+        .iter()
+        .copied()
+        .find(|(op, _)| src.starts_with(op))
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TokenKind {
     IntLiteral,
     FloatLiteral,
+    StringLiteral,
+    CharLiteral,
+
+    // `true` or `false`
+    BoolLiteral,
     Identifier,
+
+    // Loop label, `'outer`
+    Label,
     Reserved(ReservedKind),
     Punctuation(PunctuationKind),
     Operator(OperatorKind),
+
+    // Unrecognised input, already reported as a `ScannerError`
+    Error,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::IntLiteral => write!(f, "integer literal"),
+            TokenKind::FloatLiteral => write!(f, "float literal"),
+            TokenKind::StringLiteral => write!(f, "string literal"),
+            TokenKind::CharLiteral => write!(f, "char literal"),
+            TokenKind::BoolLiteral => write!(f, "bool literal"),
+            TokenKind::Identifier => write!(f, "identifier"),
+            TokenKind::Label => write!(f, "label"),
+            TokenKind::Reserved(reserved_kind) => write!(f, "`{reserved_kind}`"),
+            TokenKind::Punctuation(punctuation_kind) => write!(f, "`{punctuation_kind}`"),
+            TokenKind::Operator(operator_kind) => write!(f, "`{operator_kind}`"),
+            TokenKind::Error => write!(f, "unrecognised token"),
+        }
+    }
 }
 
 impl TokenKind {
@@ -245,20 +943,24 @@ impl TokenKind {
 }
 
 impl<'scanner> Scanner<'scanner> {
-    pub fn scan(&self) -> Result<Vec<ModuleTokenStream>, ScannerError> {
-        let determine_token_kind = |token: &Token, next_char: Option<char>| -> Option<TokenKind> {
+    /// Scan every module. Lexical errors do not stop scanning; the offending
+    /// text is kept in the token stream and all errors are returned.
+    pub fn scan(&self) -> (Vec<ModuleTokenStream<'scanner>>, Vec<ScannerError>) {
+        let determine_token_kind = |token: &Token| -> Option<TokenKind> {
             let content = &token.content;
-            assert!(content.len() > 0);
+            assert!(!content.is_empty());
 
             let starts_with = content.chars().nth(0).expect("Expected non-empty string");
             let punctuation = vec!['(', ')', '[', ']', '{', '}', ',', '.', ';'];
 
-            // Identifier or reserved keyword
-            if starts_with.is_alphabetic() {
-                if let Ok(reserved_kind) = ReservedKind::try_from(content.as_str()) {
-                    return Some(TokenKind::Reserved(reserved_kind));
+            // Identifier, reserved keyword or `BoolLiteral`
+            if starts_with.is_alphabetic() || starts_with == '_' {
+                if content == "true" || content == "false" {
+                    Some(TokenKind::BoolLiteral)
+                } else if let Ok(reserved_kind) = ReservedKind::try_from(content.as_str()) {
+                    Some(TokenKind::Reserved(reserved_kind))
                 } else {
-                    return Some(TokenKind::Identifier);
+                    Some(TokenKind::Identifier)
                 }
             }
             // `IntLiteral` or `FloatLiteral`
@@ -266,9 +968,9 @@ impl<'scanner> Scanner<'scanner> {
                 let has_decimal = content.contains('.');
 
                 if has_decimal {
-                    return Some(TokenKind::FloatLiteral);
+                    Some(TokenKind::FloatLiteral)
                 } else {
-                    return Some(TokenKind::IntLiteral);
+                    Some(TokenKind::IntLiteral)
                 }
             }
             // Punctuation
@@ -277,251 +979,749 @@ impl<'scanner> Scanner<'scanner> {
             }
             // Operators
             else {
-                let next_char = content.chars().nth(1);
-
-                let op_kind = match (starts_with, next_char) {
-                    ('<', Some('=')) => Some(OperatorKind::LTE),
-                    ('<', _) => Some(OperatorKind::LT),
-                    ('>', Some('=')) => Some(OperatorKind::LTE),
-                    ('>', _) => Some(OperatorKind::GT),
-                    (':', Some(':')) => Some(OperatorKind::TypeQualifier),
-                    ('=', Some('=')) => Some(OperatorKind::Eq),
-                    ('=', _) => Some(OperatorKind::Assign),
-                    ('+', Some('=')) => Some(OperatorKind::AssignPlus),
-                    ('-', Some('>')) => Some(OperatorKind::TypeArrow),
-                    ('-', Some('=')) => Some(OperatorKind::AssignSub),
-                    ('*', Some('=')) => Some(OperatorKind::AssignMul),
-                    ('/', Some('=')) => Some(OperatorKind::AssignDiv),
-                    ('+', _) => Some(OperatorKind::Plus),
-                    ('-', _) => Some(OperatorKind::Sub),
-                    ('*', _) => Some(OperatorKind::Mul),
-                    ('/', _) => Some(OperatorKind::Div),
+                match munch_operator(content) {
+                    Some((op, op_kind)) if op.len() == content.len() => {
+                        Some(TokenKind::Operator(op_kind))
+                    }
                     _ => None,
-                };
-
-                if op_kind.is_none() {
-                    return None;
                 }
-
-                Some(TokenKind::Operator(op_kind.unwrap()))
             }
         };
 
-        let scan_module = |module: &'scanner Module| -> Result<Vec<Token>, ScannerError> {
+        let scan_module = |module: &'scanner Module| -> (Vec<Token>, Vec<ScannerError>) {
             let content = &module.src;
             let mut token_stream: Vec<Token> = Vec::new();
-            let punctuation = vec!['(', ')', '[', ']', '{', '}', ',', ';'];
-            let operator_atoms = vec!['+', '-', '*', '/', '=', '>', '<', ':'];
+            let mut errors: Vec<ScannerError> = Vec::new();
+            let punctuation = ['(', ')', '[', ']', '{', '}', ',', ';'];
+            let operator_atoms = [
+                '+', '-', '*', '/', '%', '=', '>', '<', ':', '!', '&', '|', '^', '~',
+            ];
+
+            // Byte offset and char pairs so every token can record its exact span
+            let chars = content.char_indices().collect::<Vec<(usize, char)>>();
+
+            // Push the pending `Literal`, `Identifier`, or `Reserved` token (if any) ending at `end`
+            let flush_token = |token: &mut Token,
+                               start: usize,
+                               end: usize,
+                               token_stream: &mut Vec<Token>,
+                               errors: &mut Vec<ScannerError>| {
+                if token.content.is_empty() {
+                    return;
+                }
+
+                token.span = Span::new(module.id, start, end);
+                token.kind = match determine_token_kind(token) {
+                    Some(token_kind) => Some(token_kind),
+                    None => {
+                        errors.push(ScannerError::new(
+                            ScannerErrorKind::UnknownToken,
+                            token.span,
+                            module,
+                        ));
+                        Some(TokenKind::Error)
+                    }
+                };
+                if token.kind == Some(TokenKind::BoolLiteral) {
+                    token.literal = Some(Literal::Bool(token.content == "true"));
+                }
+
+                token_stream.push(std::mem::replace(token, Token::new()));
+            };
 
             let mut token = Token::new();
-            let mut cursor = 1;
-            let file_size = module.src.len();
-            let mut skip_char = false;
-            let mut span = Span::new();
-            for ch in content.chars() {
-                if skip_char {
-                    span.incre_col_num();
-                    cursor += 1;
-                    skip_char = false;
+            let mut token_start = 0;
+            let mut skip_chars = 0;
+            for (idx, &(offset, ch)) in chars.iter().enumerate() {
+                if skip_chars > 0 {
+                    skip_chars -= 1;
                     continue;
                 }
 
+                let next_char = chars.get(idx + 1).map(|&(_, c)| c);
+
                 if ch.is_whitespace() {
-                    // `Literal`, `Identifier`, or `Reserved` token created
-                    if token.content.len() > 0 {
-                        let next_char = content.chars().nth(cursor);
-                        let token_kind = determine_token_kind(&token, next_char);
-                        token.kind = token_kind;
-
-                        token_stream.push(token.clone());
-                        token = Token::new();
-                    }
-                } else if punctuation.contains(&ch) {
-                    // Handle case where valid token touches punctuation
-                    // if token.content == "proc" {
-                    //     // TODO: replace assert with `gemstone` error handler
-                    //     assert!(ch == '(', "Proc keyword should be followed by '('");
-                    //
-                    //     // Eat `proc` token
-                    //     token.kind = Some(TokenKind::Reserved(ReservedKind::Proc));
-                    //     token_stream.push(token.clone());
-                    //
-                    //     token = Token::new();
-                    //
-                    //     // Remember to eat `(`
-                    //     token.content.push(ch);
-                    //     token.kind = Some(TokenKind::Punctuation(PunctuationKind::OpenParen));
-                    //     token_stream.push(token.clone());
-                    //
-                    //     token = Token::new();
-                    //     continue;
-                    // }
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
+                } else if ch == '"'
+                    || (ch == '#'
+                        && token.content == "r"
+                        && raw_string_hashes(&chars[idx..]).is_some())
+                {
+                    // `"text"`, or the raw strings `r"text"` and `r#"text"#`. The
+                    // pending `r` of a raw string is part of the literal.
+                    let raw_hashes = match token.content == "r" {
+                        true => raw_string_hashes(&chars[idx..]),
+                        false => None,
+                    };
+                    let start = match raw_hashes {
+                        Some(_) => token_start,
+                        None => {
+                            flush_token(
+                                &mut token,
+                                token_start,
+                                offset,
+                                &mut token_stream,
+                                &mut errors,
+                            );
+                            offset
+                        }
+                    };
+                    token = Token::new();
 
-                    // Token that touches punctuation
-                    if token.content.len() > 0 {
-                        let next_char = content.chars().nth(cursor);
-                        let token_kind = determine_token_kind(&token, next_char);
+                    let quote_idx = idx + raw_hashes.unwrap_or(0);
+                    let scanned = scan_string(&chars[quote_idx..], raw_hashes);
+                    let byte_offset = |char_idx: usize| {
+                        chars
+                            .get(quote_idx + char_idx)
+                            .map_or(content.len(), |&(offset, _)| offset)
+                    };
+                    let end = byte_offset(scanned.len);
+                    skip_chars = quote_idx + scanned.len - idx - 1;
+
+                    for (bad_start, bad_end) in scanned.bad_escapes {
+                        errors.push(ScannerError::new(
+                            ScannerErrorKind::InvalidEscape,
+                            Span::new(module.id, byte_offset(bad_start), byte_offset(bad_end)),
+                            module,
+                        ));
+                    }
 
-                        if token_kind.is_none() {
-                            return Err(ScannerError::UnknownTokenTouchingPunctuation);
+                    token.content.push_str(&content[start..end]);
+                    token.span = Span::new(module.id, start, end);
+                    match scanned.terminated {
+                        true => {
+                            token.kind = Some(TokenKind::StringLiteral);
+                            token.literal = Some(Literal::Str(scanned.text));
+                        }
+                        false => {
+                            errors.push(ScannerError::new(
+                                ScannerErrorKind::UnterminatedString,
+                                Span::new(module.id, byte_offset(0), byte_offset(1)),
+                                module,
+                            ));
+                            token.kind = Some(TokenKind::Error);
                         }
+                    }
 
-                        token.kind = token_kind;
-                        token_stream.push(token.clone());
+                    // `StringLiteral` token created
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if let Some(len) = label_len(&chars[idx..]) {
+                    // `'outer`
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
+
+                    // `Label` token created
+                    let end = chars
+                        .get(idx + len)
+                        .map_or(content.len(), |&(offset, _)| offset);
+                    skip_chars = len - 1;
+                    token.content.push_str(&content[offset..end]);
+                    token.kind = Some(TokenKind::Label);
+                    token.span = Span::new(module.id, offset, end);
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if ch == '\'' {
+                    // `'a'` or `'\n'`
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
+
+                    let scanned = scan_char(&chars[idx..]);
+                    let byte_offset = |char_idx: usize| {
+                        chars
+                            .get(idx + char_idx)
+                            .map_or(content.len(), |&(offset, _)| offset)
+                    };
+                    let end = byte_offset(scanned.len);
+                    skip_chars = scanned.len - 1;
+
+                    token.content.push_str(&content[offset..end]);
+                    token.span = Span::new(module.id, offset, end);
+                    token.kind = Some(TokenKind::Error);
+
+                    let bad_escapes = !scanned.bad_escapes.is_empty();
+                    for (bad_start, bad_end) in scanned.bad_escapes {
+                        errors.push(ScannerError::new(
+                            ScannerErrorKind::InvalidEscape,
+                            Span::new(module.id, byte_offset(bad_start), byte_offset(bad_end)),
+                            module,
+                        ));
                     }
 
+                    match (scanned.terminated, scanned.value) {
+                        (false, _) => errors.push(ScannerError::new(
+                            ScannerErrorKind::UnterminatedChar,
+                            Span::new(module.id, offset, byte_offset(1)),
+                            module,
+                        )),
+                        (true, _) if bad_escapes => (),
+                        (true, Some(value)) => {
+                            token.kind = Some(TokenKind::CharLiteral);
+                            token.literal = Some(Literal::Char(value));
+                        }
+                        (true, None) => errors.push(ScannerError::new(
+                            ScannerErrorKind::InvalidCharLiteral,
+                            token.span,
+                            module,
+                        )),
+                    }
+
+                    // `CharLiteral` token created
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if punctuation.contains(&ch) {
+                    // Token that touches punctuation
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
+
                     // `Punctuation` token created
-                    token = Token::new();
                     token.content.push(ch);
                     token.kind = Some(TokenKind::Punctuation(PunctuationKind::from(ch)));
-                    token_stream.push(token.clone());
-
-                    // Onto the next token
-                    token = Token::new();
-                } else if operator_atoms.contains(&ch) {
+                    token.span = Span::new(module.id, offset, offset + ch.len_utf8());
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if matches!(ch, '+' | '-') && ends_in_exponent(&token.content) {
+                    // Sign of an exponent, part of the number literal
                     token.content.push(ch);
-
-                    // Check next_char
-                    let next_char = content.chars().nth(cursor);
-
-                    if ch == ':' && next_char != Some(':') {
-                        return Err(ScannerError::MalformedTQualifier);
-                    }
-
-                    if let Some(next_char) = next_char {
-                        match next_char {
-                            ':' | '=' | '>' => {
-                                skip_char = true;
-                                token.content.push(next_char)
-                            }
-                            _ => (),
+                } else if operator_atoms.contains(&ch) {
+                    // Token that touches an operator
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
+
+                    // Operators are ASCII, so their length in bytes is their length in chars
+                    match munch_operator(&content[offset..]) {
+                        Some((op, op_kind)) => {
+                            skip_chars = op.len() - 1;
+                            token.content.push_str(op);
+                            token.kind = Some(TokenKind::Operator(op_kind));
                         }
+                        None => token.content.push(ch),
+                    }
+                    token.span = Span::new(module.id, offset, offset + token.content.len());
+
+                    // A lone `:` is almost certainly a typo of `::`. Report it, but
+                    // scan it as a `TypeQualifier` so parsing is not thrown off.
+                    if token.content == ":" {
+                        errors.push(ScannerError::new(
+                            ScannerErrorKind::MalformedTQualifier,
+                            token.span,
+                            module,
+                        ));
+                        token.kind = Some(TokenKind::Operator(OperatorKind::TypeQualifier));
                     }
 
-                    let token_kind = determine_token_kind(&token, next_char);
+                    if token.kind.is_none() {
+                        errors.push(ScannerError::new(
+                            ScannerErrorKind::UnknownToken,
+                            token.span,
+                            module,
+                        ));
+                        token.kind = Some(TokenKind::Error);
+                    }
 
-                    token.kind = token_kind;
+                    // `Operator` token created
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if ch == '.' && next_char == Some('.') {
+                    // `..` ends a number literal too, so that `0..10` is a range
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
 
                     // `Operator` token created
-                    token_stream.push(token.clone());
-                    token = Token::new();
-                } else {
-                    token.content.push(ch);
-                }
+                    skip_chars = 1;
+                    token.content.push_str("..");
+                    token.kind = Some(TokenKind::Operator(OperatorKind::Range));
+                    token.span = Span::new(module.id, offset, offset + 2);
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if ch == '.' && !token.content.starts_with(|c: char| c.is_numeric()) {
+                    // Outside of a number literal `.` separates path segments and fields
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
 
-                cursor += 1;
+                    // `Punctuation` token created
+                    token.content.push(ch);
+                    token.kind = Some(TokenKind::Punctuation(PunctuationKind::Dot));
+                    token.span = Span::new(module.id, offset, offset + ch.len_utf8());
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
+                } else if ch.is_alphanumeric() || ch == '.' || ch == '_' {
+                    // `_` is part of identifiers and separates the digits of number literals
+                    if token.content.is_empty() {
+                        token_start = offset;
+                    }
+                    token.content.push(ch);
+                } else {
+                    // Unrecognised character. Keep it as an `Error` token and carry on.
+                    flush_token(
+                        &mut token,
+                        token_start,
+                        offset,
+                        &mut token_stream,
+                        &mut errors,
+                    );
 
-                // Get EOF token
-                if token.content.len() > 0 && cursor > file_size {
-                    let next_char = content.chars().nth(cursor + 1);
-                    let token_kind = determine_token_kind(&token, next_char);
-                    token.kind = token_kind;
-                    token_stream.push(token.clone());
+                    token.content.push(ch);
+                    token.kind = Some(TokenKind::Error);
+                    token.span = Span::new(module.id, offset, offset + ch.len_utf8());
+                    errors.push(ScannerError::new(
+                        ScannerErrorKind::UnknownToken,
+                        token.span,
+                        module,
+                    ));
+                    token_stream.push(std::mem::replace(&mut token, Token::new()));
                 }
             }
 
-            dbg!(&token_stream);
+            // Get EOF token
+            flush_token(
+                &mut token,
+                token_start,
+                content.len(),
+                &mut token_stream,
+                &mut errors,
+            );
 
-            Ok(token_stream)
+            (token_stream, errors)
         };
 
         // Create a vec of `ModuleTokenStream`.
         // Each `ModuleTokenStream` contains a `Module` and a `Vec<Token>
         let modules = self.module_manager.get_ref();
         let mut module_token_streams: Vec<ModuleTokenStream> = Vec::new();
+        let mut errors: Vec<ScannerError> = Vec::new();
         for module in modules.iter() {
-            let tokens = scan_module(module)?;
-            let module_token_stream = ModuleTokenStream {
+            let (tokens, module_errors) = scan_module(module);
+            errors.extend(module_errors);
+
+            let mut module_token_stream = ModuleTokenStream {
                 module,
                 tokens,
                 cursor: 0,
+                diagnostics: Vec::new(),
             };
 
             // Verify module token stream in post-pass
-            Scanner::check_module_token_stream(&module_token_stream)?;
+            errors.extend(Scanner::check_module_token_stream(&mut module_token_stream));
 
             module_token_streams.push(module_token_stream);
         }
 
-        Ok(module_token_streams)
+        (module_token_streams, errors)
     }
 }
 
-#[derive(Debug, Clone, Error)]
-pub enum ScannerError {
-    #[error("Num literal format not supported.")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ScannerErrorKind {
+    #[error("malformed number literal")]
     MalformedNumLit,
-    #[error("Type Qualifier operator expects two `:`, but found only one.")]
+    #[error("unknown suffix on number literal")]
+    InvalidNumSuffix,
+    #[error("unterminated string literal")]
+    UnterminatedString,
+    #[error("unknown escape sequence in literal")]
+    InvalidEscape,
+    #[error("unterminated char literal")]
+    UnterminatedChar,
+    #[error("char literal must hold exactly one character")]
+    InvalidCharLiteral,
+    #[error("type qualifier is `::`, found a single `:`")]
     MalformedTQualifier,
-    #[error("Malformed token. Cannot determine token touching punctuation.")]
-    UnknownTokenTouchingPunctuation,
+    #[error("unknown token")]
+    UnknownToken,
+}
+
+/// A lexical error. The offending text is still part of the token stream,
+/// usually as a `TokenKind::Error` token, so scanning continues past it.
+#[derive(Debug, Clone, Error)]
+#[error("{kind}")]
+pub struct ScannerError {
+    pub kind: ScannerErrorKind,
+    pub span: Span,
+    pub path: PathBuf,
+}
+
+impl ScannerError {
+    fn new(kind: ScannerErrorKind, span: Span, module: &Module) -> Self {
+        Self {
+            kind,
+            span,
+            path: module.path.clone(),
+        }
+    }
+}
+
+impl From<ScannerError> for Diagnostic {
+    fn from(error: ScannerError) -> Self {
+        let diagnostic = Diagnostic::error(error.to_string());
+        match error.kind {
+            ScannerErrorKind::MalformedNumLit => diagnostic
+                .with_code(codes::MALFORMED_NUM_LITERAL)
+                .with_primary(error.span, "not a valid number"),
+            ScannerErrorKind::InvalidNumSuffix => diagnostic
+                .with_code(codes::INVALID_NUM_SUFFIX)
+                .with_primary(error.span, "unknown suffix")
+                .with_help(
+                    "valid suffixes are `u8`, `u16`, `u32`, `u64`, `s8`, `s16`, `s32`, `s64`, \
+                     `f32` and `f64`",
+                ),
+            ScannerErrorKind::UnterminatedString => diagnostic
+                .with_code(codes::UNTERMINATED_STRING)
+                .with_primary(error.span, "string starts here")
+                .with_help("add a closing `\"`"),
+            ScannerErrorKind::InvalidEscape => diagnostic
+                .with_code(codes::INVALID_ESCAPE)
+                .with_primary(error.span, "unknown escape")
+                .with_help(
+                    "valid escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` and \
+                     `\\u{...}`",
+                ),
+            ScannerErrorKind::UnterminatedChar => diagnostic
+                .with_code(codes::UNTERMINATED_CHAR)
+                .with_primary(error.span, "char literal starts here")
+                .with_help("add a closing `'` on the same line"),
+            ScannerErrorKind::InvalidCharLiteral => diagnostic
+                .with_code(codes::INVALID_CHAR_LITERAL)
+                .with_primary(error.span, "not a single character")
+                .with_help("use a string literal (`\"...\"`) for text"),
+            ScannerErrorKind::MalformedTQualifier => diagnostic
+                .with_code(codes::MALFORMED_TYPE_QUALIFIER)
+                .with_primary(error.span, "expected `::`")
+                .with_suggestion(error.span, "use the type qualifier", "::"),
+            ScannerErrorKind::UnknownToken => diagnostic
+                .with_code(codes::UNKNOWN_TOKEN)
+                .with_primary(error.span, "unrecognised token"),
+        }
+    }
 }
 
 impl<'scanner> Scanner<'scanner> {
-    fn check_module_token_stream(tok_stream: &ModuleTokenStream) -> Result<(), ScannerError> {
-        Scanner::assert_no_none_token_kinds(tok_stream)?;
+    fn check_module_token_stream(tok_stream: &mut ModuleTokenStream) -> Vec<ScannerError> {
+        Scanner::assert_no_none_token_kinds(tok_stream);
         Scanner::check_num_tokens(tok_stream)
     }
 
-    fn assert_no_none_token_kinds(tok_stream: &ModuleTokenStream) -> Result<(), ScannerError> {
+    fn assert_no_none_token_kinds(tok_stream: &ModuleTokenStream) {
         for tok in tok_stream.tokens.iter() {
             assert!(
                 tok.kind.is_some(),
                 "token kind should not be none at this point. Bug in scanner. `{tok:?}`"
             );
         }
-
-        Ok(())
     }
 
-    fn check_num_tokens(tok_stream: &ModuleTokenStream) -> Result<(), ScannerError> {
+    /// Number literals are split into their parts. Malformed ones are reported
+    /// and turned into `Error` tokens.
+    fn check_num_tokens(tok_stream: &mut ModuleTokenStream) -> Vec<ScannerError> {
+        let module = tok_stream.module;
         let num_tokens = tok_stream
             .tokens
-            .iter()
+            .iter_mut()
             .filter(|t| t.kind.unwrap().is_int_literal() || t.kind.unwrap().is_float_literal());
 
-        // Helper to verify int literal format
-        let check_int_literal = |int_literal: &Token| {
-            for ch in int_literal.content.chars() {
-                // TODO: Add support for int literals with special
-                // formatting postfixes (e.g. `2i8`,
-                // `34u32`).
-                if !ch.is_numeric() {
-                    return Err(ScannerError::MalformedNumLit);
+        let mut errors: Vec<ScannerError> = Vec::new();
+        for num_tok in num_tokens {
+            match split_num_literal(&num_tok.content) {
+                Ok((kind, literal)) => {
+                    num_tok.kind = Some(kind);
+                    num_tok.literal = Some(Literal::Num(literal));
+                }
+                Err(kind) => {
+                    errors.push(ScannerError::new(kind, num_tok.span, module));
+                    num_tok.kind = Some(TokenKind::Error);
                 }
             }
+        }
 
-            Ok(())
-        };
+        errors
+    }
+}
 
-        // Helper to verify float literal format
-        let check_float_literal = |float_literal: &Token| {
-            let decimal_idx = float_literal
-                .content
-                .chars()
-                .position(|c| c == '.')
-                .expect("Expected decimal.");
-
-            for ch in float_literal.content[decimal_idx..].chars() {
-                if !ch.is_numeric() {
-                    return Err(ScannerError::MalformedNumLit);
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
 
-            Ok(())
-        };
+    use super::*;
 
-        for num_tok in num_tokens {
-            let kind = num_tok.kind.unwrap();
-            if let TokenKind::IntLiteral = kind {
-                check_int_literal(num_tok)?;
-            }
+    // Kind and content of every token of `src`, scanned as a single module
+    fn scan(src: &str) -> Vec<(TokenKind, String)> {
+        let module_manager =
+            ModuleManager::from_sources([(PathBuf::from("main.gem"), src.to_string())]);
+        let (token_streams, errors) = Scanner::new(&module_manager).scan();
+        assert!(errors.is_empty(), "{errors:?}");
+
+        token_streams[0]
+            .tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.kind.expect("Every token has a kind"),
+                    token.content.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identifiers_may_contain_underscores() {
+        use OperatorKind::{InferAssign, Plus};
+        use TokenKind::{Identifier, IntLiteral, Operator, Punctuation};
+
+        let expected = [
+            (Identifier, "my_var"),
+            (Operator(InferAssign), ":="),
+            (Identifier, "_tmp"),
+            (Operator(Plus), "+"),
+            (Identifier, "x_1_"),
+            (Operator(Plus), "+"),
+            (IntLiteral, "1_000"),
+            (Punctuation(PunctuationKind::Semicolon), ";"),
+        ];
+        let expected = expected
+            .iter()
+            .map(|&(kind, content)| (kind, content.to_string()))
+            .collect::<Vec<(TokenKind, String)>>();
+        assert_eq!(scan("my_var := _tmp + x_1_ + 1_000;"), expected);
+    }
+
+    #[test]
+    fn split_num_literal_cases() {
+        use NumSuffix::{F32, S64, U8};
+        use Radix::{Binary, Decimal, Hexadecimal, Octal};
+        use TokenKind::{FloatLiteral, IntLiteral};
+
+        let cases: &[(&str, TokenKind, Radix, &str, Option<NumSuffix>)] = &[
+            ("42", IntLiteral, Decimal, "42", None),
+            ("0b1010", IntLiteral, Binary, "1010", None),
+            ("0o17", IntLiteral, Octal, "17", None),
+            ("0xff", IntLiteral, Hexadecimal, "ff", None),
+            ("0xFF_u8", IntLiteral, Hexadecimal, "FF", Some(U8)),
+            ("1_000_000", IntLiteral, Decimal, "1000000", None),
+            ("255u8", IntLiteral, Decimal, "255", Some(U8)),
+            ("1_s64", IntLiteral, Decimal, "1", Some(S64)),
+            ("1.5", FloatLiteral, Decimal, "1.5", None),
+            ("1e3", FloatLiteral, Decimal, "1e3", None),
+            ("1.5E-3", FloatLiteral, Decimal, "1.5e-3", None),
+            ("2e+10", FloatLiteral, Decimal, "2e+10", None),
+            ("1f32", FloatLiteral, Decimal, "1", Some(F32)),
+            ("1.5e3f32", FloatLiteral, Decimal, "1.5e3", Some(F32)),
+        ];
+        for &(content, kind, radix, digits, suffix) in cases {
+            let expected = NumLiteral {
+                radix,
+                digits: digits.to_string(),
+                suffix,
+            };
+            assert_eq!(
+                split_num_literal(content),
+                Ok((kind, expected)),
+                "`{content}`"
+            );
+        }
+    }
+
+    #[test]
+    fn split_num_literal_errors() {
+        use ScannerErrorKind::{InvalidNumSuffix, MalformedNumLit};
+
+        let cases: &[(&str, ScannerErrorKind)] = &[
+            ("0x", MalformedNumLit),
+            ("0b", MalformedNumLit),
+            ("1e", MalformedNumLit),
+            ("1e+", MalformedNumLit),
+            ("1__2", MalformedNumLit),
+            ("0b102", MalformedNumLit),
+            ("0o8", MalformedNumLit),
+            ("1.2.3", MalformedNumLit),
+            ("0x1.5", MalformedNumLit),
+            ("0b1f32", MalformedNumLit),
+            ("0x1g", InvalidNumSuffix),
+            ("12abc", InvalidNumSuffix),
+            ("1u7", InvalidNumSuffix),
+            ("1a.5", InvalidNumSuffix),
+            ("1.5.", MalformedNumLit),
+        ];
+        for &(content, error) in cases {
+            assert_eq!(split_num_literal(content), Err(error), "`{content}`");
+        }
+    }
+
+    #[test]
+    fn scanning_continues_past_lexical_errors() {
+        let src = "a = 1.5.; b = 3x7; c = $ + 'ab'; d : u8 = 2;";
+
+        let module_manager =
+            ModuleManager::from_sources([(PathBuf::from("main.gem"), src.to_string())]);
+        let (token_streams, errors) = Scanner::new(&module_manager).scan();
+
+        let tokens = token_streams[0]
+            .tokens
+            .iter()
+            .map(|token| (token.kind.unwrap(), token.content.as_str()))
+            .collect::<Vec<(TokenKind, &str)>>();
+        let errors = errors
+            .iter()
+            .map(|error| (error.kind, &src[error.span.start..error.span.end]))
+            .collect::<Vec<(ScannerErrorKind, &str)>>();
+        use OperatorKind::*;
+        use PunctuationKind::Semicolon;
+        use TokenKind::*;
+
+        // Every bad piece of input becomes an `Error` token in place and the
+        // tokens after it are scanned as usual
+        assert_eq!(
+            tokens,
+            [
+                (Identifier, "a"),
+                (Operator(Assign), "="),
+                (Error, "1.5."),
+                (Punctuation(Semicolon), ";"),
+                (Identifier, "b"),
+                (Operator(Assign), "="),
+                (Error, "3x7"),
+                (Punctuation(Semicolon), ";"),
+                (Identifier, "c"),
+                (Operator(Assign), "="),
+                (Error, "$"),
+                (Operator(Plus), "+"),
+                (Error, "'ab'"),
+                (Punctuation(Semicolon), ";"),
+                (Identifier, "d"),
+                (Operator(TypeQualifier), ":"),
+                (Reserved(ReservedKind::PrimTy(ScannerPrimKind::U8)), "u8"),
+                (Operator(Assign), "="),
+                (IntLiteral, "2"),
+                (Punctuation(Semicolon), ";"),
+            ]
+        );
+        // Number literals are checked after scanning, so their errors come last
+        assert_eq!(
+            errors,
+            [
+                (ScannerErrorKind::UnknownToken, "$"),
+                (ScannerErrorKind::InvalidCharLiteral, "'ab'"),
+                (ScannerErrorKind::MalformedTQualifier, ":"),
+                (ScannerErrorKind::MalformedNumLit, "1.5."),
+                (ScannerErrorKind::InvalidNumSuffix, "3x7"),
+            ]
+        );
+    }
 
-            if let TokenKind::FloatLiteral = kind {
-                check_float_literal(num_tok)?;
+    #[test]
+    fn munch_operator_takes_the_longest() {
+        use OperatorKind::*;
+
+        let cases: &[(&str, Option<(&str, OperatorKind)>)] = &[
+            ("<<= 1", Some(("<<=", AssignShl))),
+            ("<<1", Some(("<<", Shl))),
+            ("<< =", Some(("<<", Shl))),
+            ("<a", Some(("<", LT))),
+            ("<=", Some(("<=", LTE))),
+            (">>=", Some((">>=", AssignShr))),
+            (">>", Some((">>", Shr))),
+            ("->s32", Some(("->", TypeArrow))),
+            ("-1", Some(("-", Sub))),
+            (":: s32", Some(("::", TypeQualifier))),
+            (":=", Some((":=", InferAssign))),
+            ("=<", Some(("=", Assign))),
+            ("==", Some(("==", Eq))),
+            ("!==", Some(("!=", NotEq))),
+            ("~x", Some(("~", BitNot))),
+            (":", None),
+            ("a", None),
+            ("", None),
+        ];
+        for &(src, expected) in cases {
+            assert_eq!(munch_operator(src), expected, "`{src}`");
+        }
+
+        // No operator may be listed after an operator it starts with
+        for (idx, (op, _)) in OPERATORS.iter().enumerate() {
+            for (longer, _) in &OPERATORS[idx + 1..] {
+                assert!(!longer.starts_with(op), "`{longer}` is shadowed by `{op}`");
             }
         }
+    }
+
+    #[test]
+    fn scan_escape_cases() {
+        // Text after the `\`, escaped char and chars spanned
+        let cases: &[(&str, Option<char>, usize)] = &[
+            ("n", Some('\n'), 1),
+            ("t", Some('\t'), 1),
+            ("r", Some('\r'), 1),
+            ("0", Some('\0'), 1),
+            ("\\", Some('\\'), 1),
+            ("\"", Some('"'), 1),
+            ("'", Some('\''), 1),
+            ("nx", Some('\n'), 1),
+            ("u{41}", Some('A'), 5),
+            ("u{1F48E}x", Some('💎'), 8),
+            ("u{10FFFF}", Some('\u{10FFFF}'), 9),
+            ("u{110000}", None, 9),
+            ("u{D800}", None, 7),
+            ("u{1234567}", None, 10),
+            ("u{}", None, 3),
+            ("u{41", None, 4),
+            ("u{41\"", None, 4),
+            ("u{", None, 2),
+            ("u41", None, 1),
+            ("q", None, 1),
+            ("", None, 0),
+        ];
+        for &(src, escaped, len) in cases {
+            let chars = src.char_indices().collect::<Vec<(usize, char)>>();
+            assert_eq!(scan_escape(&chars), (escaped, len), "`\\{src}`");
+        }
+    }
 
-        Ok(())
+    #[test]
+    fn bad_escapes_are_reported_in_place() {
+        let src = r#"a := "x\u{110000}y\u{41"; b := '\q';"#;
+        let module_manager =
+            ModuleManager::from_sources([(PathBuf::from("main.gem"), src.to_string())]);
+        let (_, errors) = Scanner::new(&module_manager).scan();
+
+        let errors = errors
+            .iter()
+            .map(|error| (error.kind, &src[error.span.start..error.span.end]))
+            .collect::<Vec<(ScannerErrorKind, &str)>>();
+        assert_eq!(
+            errors,
+            [
+                (ScannerErrorKind::InvalidEscape, r"\u{110000}"),
+                (ScannerErrorKind::InvalidEscape, r"\u{41"),
+                (ScannerErrorKind::InvalidEscape, r"\q"),
+            ]
+        );
     }
 }
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Where the module manager reads modules from.
+pub trait FileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Paths of the direct children (files and directories) of `path`.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    fn is_dir(&self, path: &Path) -> bool;
+}
+
+/// The real file system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiskFileSystem;
+
+impl FileSystem for DiskFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+}
+
+/// Files that only exist in memory. Directories are implied by the file paths.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFileSystem {
+    files: HashMap<PathBuf, String>,
+}
+
+/// CTOR
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl From<HashMap<PathBuf, String>> for MemoryFileSystem {
+    fn from(files: HashMap<PathBuf, String>) -> Self {
+        Self { files }
+    }
+}
+
+impl MemoryFileSystem {
+    /// Add or replace the file at `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, src: impl Into<String>) {
+        self.files.insert(path.into(), src.into());
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<String> {
+        self.files.remove(path)
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+
+        // The first component below `path` of every file inside it is a child
+        let mut children = self
+            .files
+            .keys()
+            .filter_map(|file_path| {
+                let first = file_path.strip_prefix(path).ok()?.components().next()?;
+                Some(path.join(first))
+            })
+            .collect::<Vec<PathBuf>>();
+        children.sort();
+        children.dedup();
+
+        Ok(children)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files
+            .keys()
+            .any(|file_path| file_path != path && file_path.starts_with(path))
+    }
+}
+
+/// In-memory files (e.g. unsaved editor buffers) shadowing the files of `base`.
+#[derive(Debug, Default, Clone)]
+pub struct OverlayFileSystem<Base: FileSystem> {
+    pub overlay: MemoryFileSystem,
+    pub base: Base,
+}
+
+/// CTOR
+impl<Base: FileSystem> OverlayFileSystem<Base> {
+    pub fn new(overlay: MemoryFileSystem, base: Base) -> Self {
+        Self { overlay, base }
+    }
+}
+
+impl<Base: FileSystem> FileSystem for OverlayFileSystem<Base> {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        match self.overlay.contains(path) {
+            true => self.overlay.read_to_string(path),
+            false => self.base.read_to_string(path),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        // A directory may only exist in one of the layers
+        let mut children = match (self.overlay.read_dir(path), self.base.read_dir(path)) {
+            (Err(_), Err(err)) => return Err(err),
+            (overlay, base) => overlay
+                .unwrap_or_default()
+                .into_iter()
+                .chain(base.unwrap_or_default())
+                .collect::<Vec<PathBuf>>(),
+        };
+        children.sort();
+        children.dedup();
+
+        Ok(children)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.overlay.is_dir(path) || self.base.is_dir(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(files: &[(&str, &str)]) -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem::new();
+        for &(path, src) in files {
+            fs.insert(path, src);
+        }
+        fs
+    }
+
+    #[test]
+    fn memory_reads_files() {
+        let fs = memory(&[("root/main.gem", "main")]);
+        assert_eq!(
+            fs.read_to_string(Path::new("root/main.gem")).unwrap(),
+            "main"
+        );
+
+        let err = fs.read_to_string(Path::new("root/util.gem")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn memory_directories_are_implied_by_files() {
+        let fs = memory(&[
+            ("root/main.gem", ""),
+            ("root/util/math.gem", ""),
+            ("root/util/text.gem", ""),
+        ]);
+        assert!(fs.is_dir(Path::new("root")));
+        assert!(fs.is_dir(Path::new("root/util")));
+        assert!(!fs.is_dir(Path::new("root/main.gem")));
+        assert!(!fs.is_dir(Path::new("ro")));
+
+        assert_eq!(
+            fs.read_dir(Path::new("root")).unwrap(),
+            [PathBuf::from("root/main.gem"), PathBuf::from("root/util")]
+        );
+        assert_eq!(
+            fs.read_dir(Path::new("root/util")).unwrap(),
+            [
+                PathBuf::from("root/util/math.gem"),
+                PathBuf::from("root/util/text.gem")
+            ]
+        );
+
+        let err = fs.read_dir(Path::new("other")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn memory_insert_replaces_and_remove_deletes() {
+        let mut fs = memory(&[("main.gem", "old")]);
+        fs.insert("main.gem", "new");
+        assert_eq!(fs.read_to_string(Path::new("main.gem")).unwrap(), "new");
+
+        assert_eq!(fs.remove(Path::new("main.gem")).as_deref(), Some("new"));
+        assert!(!fs.contains(Path::new("main.gem")));
+    }
+
+    #[test]
+    fn overlay_shadows_base() {
+        let base = memory(&[("root/main.gem", "on disk"), ("root/util.gem", "util")]);
+        let overlay = memory(&[("root/main.gem", "unsaved"), ("root/new/mod.gem", "new")]);
+        let fs = OverlayFileSystem::new(overlay, base);
+
+        assert_eq!(
+            fs.read_to_string(Path::new("root/main.gem")).unwrap(),
+            "unsaved"
+        );
+        assert_eq!(
+            fs.read_to_string(Path::new("root/util.gem")).unwrap(),
+            "util"
+        );
+
+        // Directories of both layers are merged
+        assert!(fs.is_dir(Path::new("root/new")));
+        assert_eq!(
+            fs.read_dir(Path::new("root")).unwrap(),
+            [
+                PathBuf::from("root/main.gem"),
+                PathBuf::from("root/new"),
+                PathBuf::from("root/util.gem")
+            ]
+        );
+        assert!(fs.read_dir(Path::new("missing")).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use span::FileId;
+
+/// Which modules import which. Modules are compiled after everything they import.
+#[derive(Debug, Default, Clone)]
+pub struct ModuleGraph {
+    dependencies: BTreeMap<FileId, Vec<FileId>>,
+}
+
+// DFS state of a module while sorting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+/// CTOR
+impl ModuleGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ModuleGraph {
+    pub fn add_module(&mut self, module: FileId) {
+        self.dependencies.entry(module).or_default();
+    }
+
+    /// Record that `module` imports `dependency`.
+    pub fn add_dependency(&mut self, module: FileId, dependency: FileId) {
+        self.add_module(dependency);
+
+        let dependencies = self.dependencies.entry(module).or_default();
+        if !dependencies.contains(&dependency) {
+            dependencies.push(dependency);
+        }
+    }
+
+    pub fn dependencies(&self, module: FileId) -> &[FileId] {
+        self.dependencies
+            .get(&module)
+            .map_or(&[], |dependencies| dependencies.as_slice())
+    }
+
+    /// Every module, ordered so that dependencies come before the modules
+    /// importing them. On failure, returns every import cycle found. Each cycle
+    /// starts and ends with the same module (e.g. `[a, b, a]`).
+    pub fn topological_order(&self) -> Result<Vec<FileId>, Vec<Vec<FileId>>> {
+        let mut visits: BTreeMap<FileId, Visit> = BTreeMap::new();
+        let mut order: Vec<FileId> = Vec::new();
+        let mut cycles: Vec<Vec<FileId>> = Vec::new();
+
+        for &module in self.dependencies.keys() {
+            let mut path: Vec<FileId> = Vec::new();
+            self.visit(module, &mut visits, &mut path, &mut order, &mut cycles);
+        }
+
+        match cycles.is_empty() {
+            true => Ok(order),
+            false => Err(cycles),
+        }
+    }
+
+    fn visit(
+        &self,
+        module: FileId,
+        visits: &mut BTreeMap<FileId, Visit>,
+        path: &mut Vec<FileId>,
+        order: &mut Vec<FileId>,
+        cycles: &mut Vec<Vec<FileId>>,
+    ) {
+        match visits.get(&module) {
+            Some(Visit::Done) => return,
+            Some(Visit::InProgress) => {
+                // Back edge: everything on the path since `module` forms a cycle
+                let cycle_start = path
+                    .iter()
+                    .position(|&visited| visited == module)
+                    .expect("Modules in progress are on the DFS path");
+                let mut cycle = path[cycle_start..].to_vec();
+                cycle.push(module);
+                cycles.push(cycle);
+                return;
+            }
+            None => (),
+        }
+
+        visits.insert(module, Visit::InProgress);
+        path.push(module);
+
+        for &dependency in self.dependencies(module) {
+            self.visit(dependency, visits, path, order, cycles);
+        }
+
+        path.pop();
+        visits.insert(module, Visit::Done);
+        order.push(module);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryFileSystem, ModuleManager};
+
+    // Graph of the modules below an in-memory root, where each module imports
+    // the modules listed for it. Imports of missing modules are returned as well.
+    fn build(modules: &[(&str, &[&str])]) -> (ModuleManager, ModuleGraph, Vec<String>) {
+        let mut fs = MemoryFileSystem::new();
+        for (name, imports) in modules {
+            let src = imports
+                .iter()
+                .map(|import| format!("import {import};\n"))
+                .collect::<String>();
+            fs.insert(format!("root/{name}.gem"), src);
+        }
+
+        let mut module_manager = ModuleManager::from_root_in(&fs, "root").unwrap();
+        let mut graph = ModuleGraph::new();
+        let mut missing: Vec<String> = Vec::new();
+        for (name, imports) in modules {
+            let module = module_manager.find_by_name(name).unwrap().id;
+            graph.add_module(module);
+
+            for import in *imports {
+                match module_manager.load_by_name(&fs, import).unwrap() {
+                    Some(dependency) => graph.add_dependency(module, dependency),
+                    None => missing.push(import.to_string()),
+                }
+            }
+        }
+
+        (module_manager, graph, missing)
+    }
+
+    fn names<'a>(module_manager: &'a ModuleManager, modules: &[FileId]) -> Vec<&'a str> {
+        modules
+            .iter()
+            .map(|&module| module_manager.get(module).name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn diamond_orders_dependencies_first() {
+        let (module_manager, graph, missing) = build(&[
+            ("main", &["left", "right"]),
+            ("left", &["base"]),
+            ("right", &["base"]),
+            ("base", &[]),
+        ]);
+        assert!(missing.is_empty());
+
+        let order = graph.topological_order().unwrap();
+        let order = names(&module_manager, &order);
+        assert_eq!(order.len(), 4, "{order:?}");
+
+        let position = |name: &str| order.iter().position(|&module| module == name).unwrap();
+        assert!(position("base") < position("left"));
+        assert!(position("base") < position("right"));
+        assert!(position("left") < position("main"));
+        assert!(position("right") < position("main"));
+    }
+
+    #[test]
+    fn duplicate_imports_are_one_dependency() {
+        let (module_manager, graph, _) = build(&[("main", &["util", "util"]), ("util", &[])]);
+
+        let main = module_manager.find_by_name("main").unwrap().id;
+        let util = module_manager.find_by_name("util").unwrap().id;
+        assert_eq!(graph.dependencies(main), [util]);
+        assert_eq!(graph.dependencies(util), []);
+    }
+
+    #[test]
+    fn self_import_is_a_cycle() {
+        let (module_manager, graph, _) = build(&[("main", &["main"])]);
+
+        let cycles = graph.topological_order().unwrap_err();
+        let cycles = cycles
+            .iter()
+            .map(|cycle| names(&module_manager, cycle))
+            .collect::<Vec<Vec<&str>>>();
+        assert_eq!(cycles, [["main", "main"]]);
+    }
+
+    #[test]
+    fn three_cycle_is_reported_once() {
+        let (module_manager, graph, _) = build(&[
+            ("a", &["b"]),
+            ("b", &["c"]),
+            ("c", &["a"]),
+            ("main", &["a"]),
+        ]);
+
+        let cycles = graph.topological_order().unwrap_err();
+        let cycles = cycles
+            .iter()
+            .map(|cycle| names(&module_manager, cycle))
+            .collect::<Vec<Vec<&str>>>();
+        assert_eq!(cycles, [["a", "b", "c", "a"]]);
+    }
+
+    #[test]
+    fn missing_module_is_not_loaded() {
+        let (module_manager, graph, missing) =
+            build(&[("main", &["util", "gone"]), ("util", &["util.deep"])]);
+        assert_eq!(missing, ["gone", "util.deep"]);
+        assert!(module_manager.find_by_name("gone").is_none());
+
+        let order = graph.topological_order().unwrap();
+        assert_eq!(names(&module_manager, &order), ["util", "main"]);
+    }
+}
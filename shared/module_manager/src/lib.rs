@@ -1,46 +1,144 @@
-use std::{ops::IndexMut, path::PathBuf};
+mod fs;
+mod graph;
+
+use std::path::{Path, PathBuf};
+
+pub use fs::{DiskFileSystem, FileSystem, MemoryFileSystem, OverlayFileSystem};
+pub use graph::ModuleGraph;
+use span::{FileId, SourceMap};
+use thiserror::Error;
+
+/// Extension every gemstone module must have.
+pub const MODULE_EXTENSION: &str = "gem";
 
 #[derive(Debug)]
 pub struct Module {
+    pub id: FileId,
     pub src: String,
     pub path: PathBuf,
+
+    // Name used to import the module (e.g. `util.math` for `<root>/util/math.gem`)
+    pub name: String,
+}
+
+#[derive(Debug, Error)]
+pub enum ModuleManagerError {
+    #[error("could not read module directory `{}`: {source}", path.display())]
+    ReadDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("could not read module `{}`: {source}", path.display())]
+    ReadModule {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("`{}` is not a gemstone module (expected a `.{MODULE_EXTENSION}` file)", path.display())]
+    NotAModule { path: PathBuf },
 }
 
 #[derive(Debug)]
 pub struct ModuleManager {
     modules: Vec<Module>,
+    source_map: SourceMap,
+
+    // Directory module names (and therefore imports) are relative to
+    root: Option<PathBuf>,
 }
 
+// CTOR
 impl ModuleManager {
-    pub fn new() -> Result<Self, std::io::Error> {
-        // Get root directory for `modules`
-        let modules_path = "modules/";
-        let dir = std::fs::read_dir(modules_path)?;
+    /// Load every module below the default `modules/` directory.
+    pub fn new() -> Result<Self, ModuleManagerError> {
+        Self::from_root("modules/")
+    }
+
+    /// Load every `.gem` module below `root` on disk, including subdirectories.
+    pub fn from_root(root: impl AsRef<Path>) -> Result<Self, ModuleManagerError> {
+        Self::from_root_in(&DiskFileSystem, root)
+    }
 
-        // Get the paths to the modules
-        let module_paths = dir
+    /// Load exactly the given module files from disk.
+    pub fn from_files(
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<Self, ModuleManagerError> {
+        Self::from_files_in(&DiskFileSystem, paths)
+    }
+
+    /// Load every `.gem` module below `root` in `fs`, including subdirectories.
+    pub fn from_root_in(
+        fs: &impl FileSystem,
+        root: impl AsRef<Path>,
+    ) -> Result<Self, ModuleManagerError> {
+        let mut module_paths: Vec<PathBuf> = Vec::new();
+        collect_module_paths(fs, root.as_ref(), &mut module_paths)?;
+
+        // Directory iteration order is platform dependent; keep `FileId`s stable
+        module_paths.sort();
+
+        Ok(Self::from_files_in(fs, module_paths)?.with_root(root))
+    }
+
+    /// Load exactly the given module files from `fs`.
+    pub fn from_files_in(
+        fs: &impl FileSystem,
+        paths: impl IntoIterator<Item = PathBuf>,
+    ) -> Result<Self, ModuleManagerError> {
+        let sources = paths
             .into_iter()
-            .filter_map(|f| f.ok().map(|f| f.path().display().to_string()))
-            .collect::<Vec<String>>();
+            .map(|path| {
+                if !is_module_path(&path) {
+                    return Err(ModuleManagerError::NotAModule { path });
+                }
 
-        // Get the actual file contents of these modules from their `modules_paths`
-        let module_srcs = module_paths
-            .iter()
-            .map(|f| std::fs::read_to_string(f).unwrap())
-            .collect::<Vec<String>>();
+                match fs.read_to_string(&path) {
+                    Ok(src) => Ok((path, src)),
+                    Err(source) => Err(ModuleManagerError::ReadModule { path, source }),
+                }
+            })
+            .collect::<Result<Vec<(PathBuf, String)>, ModuleManagerError>>()?;
+
+        Ok(Self::from_sources(sources))
+    }
 
-        let modules = module_paths
+    /// Create modules from in-memory sources. `path` is only used to name the module.
+    pub fn from_sources(sources: impl IntoIterator<Item = (PathBuf, String)>) -> Self {
+        // Register the original text of every module so spans can be mapped back to it
+        let mut source_map = SourceMap::new();
+        let modules = sources
             .into_iter()
-            .zip(module_srcs)
-            .map(|(f, src)| Module {
-                src,
-                path: PathBuf::from(f),
+            .map(|(path, src)| {
+                let id = source_map.add_file(path.clone(), src.clone());
+                let name = module_name(None, &path);
+                Module {
+                    id,
+                    src,
+                    path,
+                    name,
+                }
             })
             .collect::<Vec<Module>>();
 
-        Ok(ModuleManager { modules })
+        ModuleManager {
+            modules,
+            source_map,
+            root: None,
+        }
     }
 
+    /// Name modules (and resolve imports) relative to `root`.
+    pub fn with_root(mut self, root: impl AsRef<Path>) -> Self {
+        let root = root.as_ref().to_path_buf();
+        for module in self.modules.iter_mut() {
+            module.name = module_name(Some(&root), &module.path);
+        }
+
+        self.root = Some(root);
+        self
+    }
+}
+
+impl ModuleManager {
     pub fn get_ref(&self) -> &Vec<Module> {
         &self.modules
     }
@@ -48,4 +146,239 @@ impl ModuleManager {
     pub fn get_mut_ref(&mut self) -> &mut Vec<Module> {
         &mut self.modules
     }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
+    pub fn get(&self, id: FileId) -> &Module {
+        self.modules
+            .iter()
+            .find(|module| module.id == id)
+            .expect("Module ids are handed out by this module manager")
+    }
+
+    /// Module imported as `name` (e.g. `util.math`), if it was loaded.
+    pub fn find_by_name(&self, name: &str) -> Option<&Module> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+
+    /// Load the module imported as `name` from `fs` unless it is already loaded.
+    /// Returns `None` if there is no such module.
+    pub fn load_by_name(
+        &mut self,
+        fs: &impl FileSystem,
+        name: &str,
+    ) -> Result<Option<FileId>, ModuleManagerError> {
+        if let Some(module) = self.find_by_name(name) {
+            return Ok(Some(module.id));
+        }
+
+        let path = self.path_for_name(name);
+        let src = match fs.read_to_string(&path) {
+            Ok(src) => src,
+            Err(source) if source.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(ModuleManagerError::ReadModule { path, source }),
+        };
+
+        let id = self.source_map.add_file(path.clone(), src.clone());
+        self.modules.push(Module {
+            id,
+            src,
+            path,
+            name: name.to_string(),
+        });
+
+        Ok(Some(id))
+    }
+
+    /// Where the module imported as `name` is expected to live.
+    pub fn path_for_name(&self, name: &str) -> PathBuf {
+        let relative = name
+            .split('.')
+            .collect::<PathBuf>()
+            .with_extension(MODULE_EXTENSION);
+
+        match &self.root {
+            Some(root) => root.join(relative),
+            None => relative,
+        }
+    }
+}
+
+// `<root>/util/math.gem` is named `util.math`. Modules outside of the root are
+// named after their file stem.
+fn module_name(root: Option<&Path>, path: &Path) -> String {
+    match root.and_then(|root| path.strip_prefix(root).ok()) {
+        Some(relative) => relative
+            .with_extension("")
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("."),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+fn is_module_path(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == MODULE_EXTENSION)
+}
+
+// Recursively collect the paths of all modules below `dir`
+fn collect_module_paths(
+    fs: &impl FileSystem,
+    dir: &Path,
+    module_paths: &mut Vec<PathBuf>,
+) -> Result<(), ModuleManagerError> {
+    let read_dir_error = |source| ModuleManagerError::ReadDir {
+        path: dir.to_path_buf(),
+        source,
+    };
+
+    for path in fs.read_dir(dir).map_err(read_dir_error)? {
+        if fs.is_dir(&path) {
+            collect_module_paths(fs, &path, module_paths)?;
+        } else if is_module_path(&path) {
+            module_paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(files: &[&str]) -> MemoryFileSystem {
+        let mut fs = MemoryFileSystem::new();
+        for &path in files {
+            fs.insert(path, format!("// {path}"));
+        }
+        fs
+    }
+
+    fn names(module_manager: &ModuleManager) -> Vec<(usize, &str)> {
+        module_manager
+            .get_ref()
+            .iter()
+            .map(|module| (module.id.0, module.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn root_is_collected_recursively_and_sorted() {
+        let fs = memory(&[
+            "modules/main.gem",
+            "modules/util/math.gem",
+            "modules/util/deep/io.gem",
+            "modules/alpha.gem",
+            "modules/notes.txt",
+            "modules/util/math.gem.bak",
+            "elsewhere.gem",
+        ]);
+
+        let module_manager = ModuleManager::from_root_in(&fs, "modules").unwrap();
+        assert_eq!(
+            names(&module_manager),
+            [
+                (0, "alpha"),
+                (1, "main"),
+                (2, "util.deep.io"),
+                (3, "util.math"),
+            ]
+        );
+        assert_eq!(module_manager.root(), Some(Path::new("modules")));
+
+        let math = module_manager.find_by_name("util.math").unwrap();
+        assert_eq!(math.path, Path::new("modules/util/math.gem"));
+        assert_eq!(math.src, "// modules/util/math.gem");
+        assert_eq!(module_manager.source_map().get(math.id).src, math.src);
+    }
+
+    #[test]
+    fn missing_root_is_a_read_dir_error() {
+        let fs = memory(&["modules/main.gem"]);
+        let err = ModuleManager::from_root_in(&fs, "src").unwrap_err();
+        assert!(
+            matches!(err, ModuleManagerError::ReadDir { path, .. } if path == Path::new("src"))
+        );
+    }
+
+    #[test]
+    fn explicit_files_keep_their_order() {
+        let fs = memory(&["b.gem", "lib/a.gem"]);
+        let module_manager =
+            ModuleManager::from_files_in(&fs, [PathBuf::from("b.gem"), PathBuf::from("lib/a.gem")])
+                .unwrap();
+
+        // Without a root modules are named after their file stem
+        assert_eq!(names(&module_manager), [(0, "b"), (1, "a")]);
+        assert_eq!(module_manager.root(), None);
+    }
+
+    #[test]
+    fn explicit_files_must_be_modules() {
+        let fs = memory(&["main.gem", "notes.txt"]);
+        let err = ModuleManager::from_files_in(
+            &fs,
+            [PathBuf::from("main.gem"), PathBuf::from("notes.txt")],
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, ModuleManagerError::NotAModule { path } if path == Path::new("notes.txt"))
+        );
+    }
+
+    #[test]
+    fn missing_explicit_file_is_a_read_module_error() {
+        let fs = memory(&["main.gem"]);
+        let err = ModuleManager::from_files_in(&fs, [PathBuf::from("missing.gem")]).unwrap_err();
+        assert!(matches!(
+            err,
+            ModuleManagerError::ReadModule { path, source }
+                if path == Path::new("missing.gem") && source.kind() == std::io::ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn module_names_are_relative_to_the_root() {
+        let root = Path::new("modules");
+        assert_eq!(
+            module_name(Some(root), Path::new("modules/util/math.gem")),
+            "util.math"
+        );
+        assert_eq!(
+            module_name(Some(root), Path::new("modules/main.gem")),
+            "main"
+        );
+        assert_eq!(
+            module_name(Some(root), Path::new("other/util/io.gem")),
+            "io"
+        );
+        assert_eq!(
+            module_name(None, Path::new("modules/util/math.gem")),
+            "math"
+        );
+    }
+
+    #[test]
+    fn names_map_back_to_paths() {
+        let module_manager = ModuleManager::from_sources([]).with_root("modules");
+        assert_eq!(
+            module_manager.path_for_name("util.math"),
+            Path::new("modules/util/math.gem")
+        );
+        assert_eq!(
+            ModuleManager::from_sources([]).path_for_name("main"),
+            Path::new("main.gem")
+        );
+    }
 }
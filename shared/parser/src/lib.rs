@@ -1,140 +1,397 @@
-#![feature(core_intrinsics)]
-
+use diagnostics::{Diagnostic, codes};
 use lexical_analyzer::{
     ModuleTokenStream, OperatorKind, PunctuationKind,
-    ReservedKind::{Enum, Proc, Struct},
+    ReservedKind::{self, Enum, Import as ImportKw, Proc, Pub, Struct},
     Token, TokenKind,
 };
-use typer::{Type, eval_ty_from_token};
+use span::{FileId, Span};
 
 #[derive(Debug)]
 pub struct Ast {
-    program: Program,
+    pub program: Program,
 }
 
 impl Ast {
-    pub fn new(program: Vec<ModuleTokenStream>) -> Self {
-        let program = Program::new(program);
-        Self { program }
+    /// Parse every module. Syntax errors are recovered from, so the returned
+    /// AST may be partial; it is only complete when no errors are returned.
+    pub fn new(program: Vec<ModuleTokenStream>) -> (Self, Vec<Diagnostic>) {
+        let (program, diagnostics) = Program::new(program);
+        (Self { program }, diagnostics)
     }
 }
 
 #[derive(Debug)]
-struct Program {
-    modules: Vec<Module>,
+pub struct Program {
+    pub modules: Vec<Module>,
 }
 
 impl Program {
-    fn new(module_token_streams: Vec<ModuleTokenStream>) -> Self {
+    fn new(module_token_streams: Vec<ModuleTokenStream>) -> (Self, Vec<Diagnostic>) {
         let mut modules: Vec<Module> = Vec::new();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
         for module_token_stream in module_token_streams {
-            modules.push(Module::new(module_token_stream));
+            let (module, module_diagnostics) = Module::new(module_token_stream);
+            modules.push(module);
+            diagnostics.extend(module_diagnostics);
         }
 
-        Self { modules }
+        (Self { modules }, diagnostics)
     }
 }
 
 #[derive(Debug)]
-struct Module {
-    declarations: Vec<Declaration>,
+pub struct Module {
+    pub id: FileId,
+    pub imports: Vec<Import>,
+    pub declarations: Vec<Declaration>,
 }
 
 // fn get_token_from_kind(token: Token)
 
 impl Module {
-    fn new(mut module: ModuleTokenStream) -> Self {
-        let mut declarations = parse_declarations(module, true);
-        Self { declarations }
+    fn new(mut module: ModuleTokenStream) -> (Self, Vec<Diagnostic>) {
+        let (imports, declarations) = parse_items(&mut module);
+        let module_ast = Self {
+            id: module.module.id,
+            imports,
+            declarations,
+        };
+
+        (module_ast, module.diagnostics)
     }
 }
 
-fn parse_declarations(mut module_tokens: ModuleTokenStream, parsing: bool) -> Vec<Declaration> {
+fn parse_items(module_tokens: &mut ModuleTokenStream) -> (Vec<Import>, Vec<Declaration>) {
+    let mut imports: Vec<Import> = Vec::new();
     let mut declarations: Vec<Declaration> = Vec::new();
-    let mut parsing = true;
-    while parsing {
-        let name = module_tokens
-            .get_token()
-            .expect("Expected `Identifier` token, found `None`")
-            .assert_allowed_kinds(&[
-                TokenKind::Identifier,
-                TokenKind::Reserved(lexical_analyzer::ReservedKind::Main),
-            ]);
-
-        let _ = module_tokens
-            .get_token()
-            .expect("Expected `TypeQualifier` token, found `None`")
-            .assert_kind(TokenKind::Operator(OperatorKind::TypeQualifier));
-
-        // TODO: pull this out into a `parse_type()` function
-        // to handle the case of function-types (e.g. (s32, s32) -> s32).
-        let ty_tok = module_tokens
-            .get_token()
-            .expect("Expected `Type` token, found `None`")
-            .assert_allowed_kinds(&[
-                TokenKind::Reserved(Struct),
-                TokenKind::Reserved(Enum),
-                TokenKind::Reserved(Proc),
-            ]);
-
-        // TODO: Handle function types
-        // NOTE: This function eats tokens!
-        let ty = typer::eval_ty_from_token(ty_tok, Some(&mut module_tokens));
-        let decl_ty = ty.clone();
-
-        let decl_signature = DeclarationSignature::new(name, ty);
-        println!("Parsed declaration signature: {decl_signature:?}");
-
-        let _l_brace = module_tokens
-            .get_token()
-            .expect("Expected `{` token, found `None`")
-            .assert_kind(TokenKind::Punctuation(PunctuationKind::OpenBrace));
-
-        let decl_def = parse_declaration_def(&mut module_tokens, decl_ty, parsing);
-        println!("Parsed declaration definition: {decl_def:?}");
-
-        let _r_brace = module_tokens
-            .get_token()
-            .expect("Expected `}` token, found `None`")
-            .assert_kind(TokenKind::Punctuation(PunctuationKind::CloseBrace));
-
-        // `Declaration` parsed
-        declarations.push(Declaration {
-            sig: decl_signature,
-            def: decl_def,
+    while module_tokens.peek_token().is_some() {
+        let item_start = module_tokens.cursor;
+        let parsed = match next_token_is(module_tokens, TokenKind::Reserved(ImportKw)) {
+            true => parse_import(module_tokens).map(|import| imports.push(import)),
+            false => parse_declaration(module_tokens).map(|decl| declarations.push(decl)),
+        };
+
+        if let Err(diagnostic) = parsed {
+            module_tokens.report(*diagnostic);
+            synchronize_declaration(module_tokens);
+
+            // A stray closing bracket recovery stopped at
+            if module_tokens.cursor == item_start {
+                consume_next_token(module_tokens);
+            }
+        }
+    }
+
+    (imports, declarations)
+}
+
+/// Error recovery: skip to the start of the next top-level declaration.
+fn synchronize_declaration(module_tokens: &mut ModuleTokenStream) {
+    use PunctuationKind::{CloseBrace, Semicolon};
+
+    loop {
+        module_tokens.skip_until(|module_tokens| {
+            starts_declaration(module_tokens)
+                || module_tokens.next_is(TokenKind::Punctuation(Semicolon))
         });
 
-        // See if we are done parsing
-        if std::intrinsics::unlikely(module_tokens.peek_token().is_none()) {
-            parsing = false;
+        // A stray `;` or the `}` closing the broken declaration's body ends it.
+        // Keep skipping if what follows is not a declaration either.
+        match module_tokens.peek_nth_kind(0) {
+            Some(TokenKind::Punctuation(Semicolon | CloseBrace)) => {
+                consume_next_token(module_tokens);
+                if starts_declaration(module_tokens) {
+                    return;
+                }
+            }
+            _ => return,
         }
     }
+}
+
+// `name ::` (or `main ::`), optionally preceded by `pub`, begins every
+// top-level declaration, as does `import`
+fn starts_declaration(module_tokens: &ModuleTokenStream) -> bool {
+    let name_idx = usize::from(module_tokens.next_is(TokenKind::Reserved(Pub)));
+    let is_named_declaration = matches!(
+        module_tokens.peek_nth_kind(name_idx),
+        Some(TokenKind::Identifier | TokenKind::Reserved(ReservedKind::Main))
+    ) && module_tokens.peek_nth_kind(name_idx + 1)
+        == Some(TokenKind::Operator(OperatorKind::TypeQualifier));
 
-    declarations
+    is_named_declaration || module_tokens.next_is(TokenKind::Reserved(ImportKw))
+}
+
+// `import util.math;`
+fn parse_import(module_tokens: &mut ModuleTokenStream) -> Result<Import, Box<Diagnostic>> {
+    use PunctuationKind::{Dot, Semicolon};
+    use TokenKind::{Identifier, Punctuation, Reserved};
+
+    let import_tok = expect_next(module_tokens, &[Reserved(ImportKw)])?;
+
+    let mut path = vec![expect_next(module_tokens, &[Identifier])?];
+    while next_token_is(module_tokens, Punctuation(Dot)) {
+        consume_next_token(module_tokens);
+        path.push(expect_next(module_tokens, &[Identifier])?);
+    }
+
+    let semicolon = expect_next(module_tokens, &[Punctuation(Semicolon)])?;
+
+    Ok(Import {
+        path,
+        span: import_tok.span.to(semicolon.span),
+    })
+}
+
+// Optional `pub`. Everything is private to its module by default.
+fn parse_visibility(module_tokens: &mut ModuleTokenStream) -> Visibility {
+    match next_token_is(module_tokens, TokenKind::Reserved(Pub)) {
+        true => {
+            consume_next_token(module_tokens);
+            Visibility::Public
+        }
+        false => Visibility::Private,
+    }
+}
+
+fn parse_declaration(
+    module_tokens: &mut ModuleTokenStream,
+) -> Result<Declaration, Box<Diagnostic>> {
+    let vis = parse_visibility(module_tokens);
+    let name = expect_next(
+        module_tokens,
+        &[
+            TokenKind::Identifier,
+            TokenKind::Reserved(ReservedKind::Main),
+        ],
+    )?;
+
+    let _ = expect_next(
+        module_tokens,
+        &[TokenKind::Operator(OperatorKind::TypeQualifier)],
+    )?;
+
+    let ty_tok = expect_next(
+        module_tokens,
+        &[
+            TokenKind::Reserved(Struct),
+            TokenKind::Reserved(Enum),
+            TokenKind::Reserved(Proc),
+        ],
+    )?;
+
+    // NOTE: A `proc` signature eats the tokens of its parameter list
+    let ty = match ty_tok.kind {
+        Some(TokenKind::Reserved(Struct)) => TypeExpr::Struct,
+        Some(TokenKind::Reserved(Enum)) => TypeExpr::Enum,
+        _ => parse_proc_ty(module_tokens)?,
+    };
+
+    let decl_signature = DeclarationSignature::new(name, ty);
+    log::debug!("Parsed declaration signature: {decl_signature:?}");
+
+    let _l_brace = expect_next(
+        module_tokens,
+        &[TokenKind::Punctuation(PunctuationKind::OpenBrace)],
+    )?;
+
+    // NOTE: Errors inside the body are recovered from by the definition parsers
+    let decl_def = parse_declaration_def(module_tokens, &decl_signature.ty);
+    log::debug!("Parsed declaration definition: {decl_def:?}");
+
+    let _r_brace = expect_next(
+        module_tokens,
+        &[TokenKind::Punctuation(PunctuationKind::CloseBrace)],
+    )?;
+
+    // `Declaration` parsed
+    Ok(Declaration {
+        vis,
+        sig: decl_signature,
+        def: decl_def,
+    })
 }
 
 fn parse_declaration_def(
     module_tokens: &mut ModuleTokenStream<'_>,
-    decl_ty: Type,
-    parsing: bool,
+    decl_ty: &TypeExpr,
 ) -> DeclarationDef {
     match decl_ty {
-        Type::Prim(primitive) => todo!(),
-        Type::Struct => parse_struct_decl_def(module_tokens),
-        Type::Enum => parse_enum_decl_def(module_tokens),
-        Type::Function { inputs, output } => todo!(),
+        TypeExpr::Struct => parse_struct_decl_def(module_tokens),
+        TypeExpr::Enum => parse_enum_decl_def(module_tokens),
+        TypeExpr::Proc { .. } => parse_proc_decl_def(module_tokens),
+        TypeExpr::Prim(_) | TypeExpr::Named(_) => {
+            unreachable!("Declarations are structs, enums or procs")
+        }
+    }
+}
+
+/// Parse the type of a field or parameter (e.g. `s32`, `Point` or `proc (x :: s32) -> s32`).
+fn parse_type(module_tokens: &mut ModuleTokenStream<'_>) -> Result<TypeExpr, Box<Diagnostic>> {
+    // Only peek, so that the delimiter ending a field or parameter is not eaten
+    let token = next_token(module_tokens)?;
+    let ty = match token.kind {
+        Some(TokenKind::Reserved(ReservedKind::PrimTy(_))) => TypeExpr::Prim(token),
+        Some(TokenKind::Identifier) => TypeExpr::Named(token),
+        Some(TokenKind::Reserved(Proc)) => {
+            consume_next_token(module_tokens);
+            return parse_proc_ty(module_tokens);
+        }
+        _ => {
+            return Err(Box::new(
+                Diagnostic::error(format!("expected a type, found {}", token.describe()))
+                    .with_code(codes::NOT_A_TYPE)
+                    .with_primary(token.span, "expected a type"),
+            ));
+        }
+    };
+
+    consume_next_token(module_tokens);
+    Ok(ty)
+}
+
+// Everything of a `proc` type after the keyword: `(x :: s32, y :: s32) -> s32`
+fn parse_proc_ty(module_tokens: &mut ModuleTokenStream<'_>) -> Result<TypeExpr, Box<Diagnostic>> {
+    use PunctuationKind::{CloseBrace, CloseBracket, CloseParen, Comma, OpenBrace, OpenParen};
+    use TokenKind::{Operator, Punctuation};
+
+    let _l_parn = expect_next(module_tokens, &[Punctuation(OpenParen)])?;
+
+    let mut params: Vec<Param> = Vec::new();
+
+    // Tokens that can only appear after the parameter list
+    let ends_parameters = |module_tokens: &ModuleTokenStream| {
+        module_tokens.next_is(Punctuation(OpenBrace))
+            || module_tokens.next_is(Operator(OperatorKind::TypeArrow))
+    };
+
+    loop {
+        if module_tokens.peek_token().is_none() {
+            return Err(Box::new(module_tokens.eof_error()));
+        }
+
+        if next_token_is(module_tokens, Punctuation(CloseParen)) {
+            consume_next_token(module_tokens);
+            break;
+        }
+
+        // Unclosed parameter list, or one closed by the wrong bracket. Report it,
+        // but carry on with the signature.
+        let wrong_close = matches!(
+            module_tokens.peek_nth_kind(0),
+            Some(Punctuation(CloseBracket | CloseBrace))
+        );
+        if wrong_close || ends_parameters(module_tokens) {
+            let token = module_tokens.peek_token().unwrap();
+            module_tokens.report(
+                Diagnostic::error(format!("expected `)`, found {}", token.describe()))
+                    .with_code(codes::UNEXPECTED_TOKEN)
+                    .with_primary(token.span, "parameter list is not closed"),
+            );
+            if wrong_close {
+                consume_next_token(module_tokens);
+            }
+            break;
+        }
+
+        match parse_param(module_tokens) {
+            Ok(param) => params.push(param),
+            Err(diagnostic) => {
+                module_tokens.report(*diagnostic);
+
+                // Resynchronise at the next parameter
+                module_tokens.skip_until(|module_tokens| {
+                    module_tokens.next_is(Punctuation(Comma)) || ends_parameters(module_tokens)
+                });
+            }
+        }
+
+        // Optional comma. Eat if found.
+        if next_token_is(module_tokens, Punctuation(Comma)) {
+            consume_next_token(module_tokens);
+        }
     }
+
+    // Optional return type
+    let output = match next_token_is(module_tokens, Operator(OperatorKind::TypeArrow)) {
+        true => {
+            consume_next_token(module_tokens);
+            Some(Box::new(parse_type(module_tokens)?))
+        }
+        false => None,
+    };
+
+    Ok(TypeExpr::Proc { params, output })
+}
+
+fn parse_param(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Param, Box<Diagnostic>> {
+    let name = expect_next(module_tokens, &[TokenKind::Identifier])?;
+
+    let _ty_punc = expect_next(
+        module_tokens,
+        &[TokenKind::Operator(OperatorKind::TypeQualifier)],
+    )?;
+
+    let ty = parse_type(module_tokens)?;
+    Ok(Param { name, ty })
 }
 
 fn next_token_is(module_tokens: &mut ModuleTokenStream<'_>, token_kind: TokenKind) -> bool {
-    let token = module_tokens.peek_token().unwrap();
-    let token_kind_found = token.kind.unwrap();
-    token_kind == token_kind_found
+    module_tokens.next_is(token_kind)
 }
 
 fn consume_next_token(module_tokens: &mut ModuleTokenStream<'_>) {
     // Consume the token
-    let _ = module_tokens.get_token().unwrap();
+    let _ = module_tokens.get_token();
+}
+
+// Consume the next token if it is one of `kinds`. Otherwise it is left in the
+// stream, so a closing delimiter is still there for error recovery.
+fn expect_next(
+    module_tokens: &mut ModuleTokenStream<'_>,
+    kinds: &[TokenKind],
+) -> Result<Token, Box<Diagnostic>> {
+    let token = next_token(module_tokens)?.expect_allowed_kinds(kinds)?;
+    consume_next_token(module_tokens);
+    Ok(token)
+}
+
+/// Error recovery inside a list closed by `close` (fields, variants or statements): skip until `stop` holds or the list is closed. Other closing
+/// brackets are left over from the broken item, so they are skipped as well.
+fn synchronize_item(
+    module_tokens: &mut ModuleTokenStream<'_>,
+    close: PunctuationKind,
+    stop: impl Fn(&ModuleTokenStream) -> bool,
+) {
+    use PunctuationKind::{CloseBrace, CloseBracket, CloseParen};
+
+    loop {
+        module_tokens.skip_until(&stop);
+        match module_tokens.peek_nth_kind(0) {
+            Some(TokenKind::Punctuation(kind @ (CloseParen | CloseBracket | CloseBrace)))
+                if kind != close =>
+            {
+                consume_next_token(module_tokens)
+            }
+            _ => return,
+        }
+    }
+}
+
+// Peek the next token. Running out of tokens is reported as an error.
+fn next_token(module_tokens: &ModuleTokenStream<'_>) -> Result<Token, Box<Diagnostic>> {
+    module_tokens
+        .peek_token()
+        .ok_or_else(|| Box::new(module_tokens.eof_error()))
+}
+
+// Body of a struct, enum or proc is over (or the module ended early)
+fn at_body_end(module_tokens: &mut ModuleTokenStream<'_>) -> bool {
+    module_tokens.peek_token().is_none()
+        || next_token_is(
+            module_tokens,
+            TokenKind::Punctuation(PunctuationKind::CloseBrace),
+        )
 }
 
 fn parse_enum_decl_def(module_tokens: &mut ModuleTokenStream<'_>) -> DeclarationDef {
@@ -143,106 +400,903 @@ fn parse_enum_decl_def(module_tokens: &mut ModuleTokenStream<'_>) -> Declaration
 
     let mut variants: Vec<Variant> = Vec::new();
 
-    // We have an empty enum if we immedately find a `CloseBrace` token
-    if next_token_is(module_tokens, Punctuation(CloseBrace)) {
-        consume_next_token(module_tokens);
-        return DeclarationDef::Enum { variants };
-    }
+    // Try to parse enum variants. We have an empty enum if we immedately find a
+    // `CloseBrace` token. Note that we do not consume the closing brace here.
+    // Instead, we consume it in the `parse_declaration()` function further
+    // the call stack.
+    while !at_body_end(module_tokens) {
+        let vis = parse_visibility(module_tokens);
+        match expect_next(module_tokens, &[Identifier]) {
+            Ok(variant) => variants.push(Variant { vis, name: variant }),
+            Err(diagnostic) => {
+                module_tokens.report(*diagnostic);
 
-    // Try to parse enum variants
-    while let Some(token) = module_tokens.get_token() {
-        token.assert_kind(Identifier);
-        let variant = token;
-        variants.push(Variant { name: variant });
+                // Resynchronise at the next variant
+                synchronize_item(module_tokens, CloseBrace, |module_tokens| {
+                    module_tokens.next_is(Punctuation(Comma))
+                });
+            }
+        }
 
         // Check for comma
         if next_token_is(module_tokens, Punctuation(Comma)) {
             consume_next_token(module_tokens);
         }
-
-        // Check for closing brace
-        let token = module_tokens.peek_token().unwrap();
-        let token_kind = token.kind.unwrap();
-        if token_kind == Punctuation(CloseBrace) {
-            // Note that we do not consume the token here.
-            // Instead, we consume it in the `parse_declarations()` function further
-            // the call stack.
-            break;
-        }
     }
 
     DeclarationDef::Enum { variants }
 }
 
 fn parse_struct_decl_def(module_tokens: &mut ModuleTokenStream<'_>) -> DeclarationDef {
-    use OperatorKind::TypeQualifier;
     use PunctuationKind::{CloseBrace, Comma};
-    use TokenKind::{Identifier, Operator, Punctuation};
+    use TokenKind::{Identifier, Operator, Punctuation, Reserved};
 
     let mut fields: Vec<Field> = Vec::new();
 
-    // We have an empty struct if we immedately find a `CloseBrace` token
-    if next_token_is(module_tokens, Punctuation(CloseBrace)) {
-        consume_next_token(module_tokens);
-        return DeclarationDef::Struct { fields };
+    // Try to parse struct fields. We have an empty struct if we immedately find a
+    // `CloseBrace` token. Note that we do not consume the closing brace here.
+    // Instead, we consume it in the `parse_declaration()` function further
+    // the call stack.
+    while !at_body_end(module_tokens) {
+        match parse_field(module_tokens) {
+            Ok(field) => fields.push(field),
+            Err(diagnostic) => {
+                module_tokens.report(*diagnostic);
+
+                // Resynchronise at the next field (`,`, `pub` or `name ::`)
+                synchronize_item(module_tokens, CloseBrace, |module_tokens| {
+                    module_tokens.next_is(Punctuation(Comma))
+                        || module_tokens.next_is(Reserved(Pub))
+                        || (module_tokens.next_is(Identifier)
+                            && module_tokens.peek_nth_kind(1)
+                                == Some(Operator(OperatorKind::TypeQualifier)))
+                });
+            }
+        }
+
+        // Check for comma
+        if next_token_is(module_tokens, Punctuation(Comma)) {
+            consume_next_token(module_tokens);
+        }
     }
 
-    // Struct not empty
-    while let Some(token) = module_tokens.get_token() {
-        // declaration name
-        token.assert_kind(Identifier);
-        let name = token;
+    DeclarationDef::Struct { fields }
+}
 
-        // `::`
-        let _ = module_tokens
-            .get_token()
-            .unwrap()
-            .assert_kind(Operator(TypeQualifier));
+fn parse_field(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Field, Box<Diagnostic>> {
+    use OperatorKind::TypeQualifier;
+    use TokenKind::{Identifier, Operator};
 
-        // Declaration kind
-        let ty_tok = module_tokens.get_token().unwrap();
+    let vis = parse_visibility(module_tokens);
 
-        // Type eval for this declaration kind (i.e. is it a struct or an enum?)
-        let ty = eval_ty_from_token(ty_tok, None);
-        fields.push(Field::new(name, ty));
+    // declaration name
+    let name = expect_next(module_tokens, &[Identifier])?;
 
-        // Check for comma
-        if next_token_is(module_tokens, Punctuation(Comma)) {
+    // `::`
+    let _ = expect_next(module_tokens, &[Operator(TypeQualifier)])?;
+
+    // Field type
+    let ty = parse_type(module_tokens)?;
+    Ok(Field::new(vis, name, ty))
+}
+
+fn parse_proc_decl_def(module_tokens: &mut ModuleTokenStream<'_>) -> DeclarationDef {
+    // Like struct and enum bodies, the closing brace is left to `parse_declaration()`
+    let (body, expr) = parse_body_items(module_tokens);
+
+    DeclarationDef::Function {
+        def: FunctionDef { body, expr },
+    }
+}
+
+// Statements and tail expression up to the closing brace of a proc body or block
+fn parse_body_items(
+    module_tokens: &mut ModuleTokenStream<'_>,
+) -> (Vec<Statement>, Option<Expression>) {
+    use PunctuationKind::{CloseBrace, Semicolon};
+    use TokenKind::Punctuation;
+
+    let mut body: Vec<Statement> = Vec::new();
+    let mut expr: Option<Expression> = None;
+
+    while !at_body_end(module_tokens) {
+        // Empty statement
+        if next_token_is(module_tokens, Punctuation(Semicolon)) {
             consume_next_token(module_tokens);
+            continue;
+        }
+
+        match parse_body_item(module_tokens) {
+            Ok(BodyItem::Statement(statement)) => body.push(statement),
+            Ok(BodyItem::Tail(tail)) => expr = Some(tail),
+            Err(diagnostic) => {
+                module_tokens.report(*diagnostic);
+
+                // Resynchronise after the end of the broken statement
+                synchronize_item(module_tokens, CloseBrace, |module_tokens| {
+                    module_tokens.next_is(Punctuation(Semicolon))
+                });
+                if next_token_is(module_tokens, Punctuation(Semicolon)) {
+                    consume_next_token(module_tokens);
+                }
+            }
+        }
+    }
+
+    (body, expr)
+}
+
+// Item of a proc body
+enum BodyItem {
+    Statement(Statement),
+
+    // Expression without a `;` closing the body. It is the implicit return value.
+    Tail(Expression),
+}
+
+fn parse_body_item(module_tokens: &mut ModuleTokenStream<'_>) -> Result<BodyItem, Box<Diagnostic>> {
+    use TokenKind::{Operator, Reserved};
+
+    let statement = if next_token_is(module_tokens, Reserved(ReservedKind::Return)) {
+        // `return;` or `return expr;`
+        let return_tok = module_tokens.expect_token()?;
+        let value = match next_token_is(
+            module_tokens,
+            TokenKind::Punctuation(PunctuationKind::Semicolon),
+        ) || at_body_end(module_tokens)
+        {
+            true => None,
+            false => Some(parse_expression(module_tokens)?),
+        };
+
+        let span = value
+            .as_ref()
+            .map_or(return_tok.span, |value| return_tok.span.to(value.span()));
+        Statement::Return { value, span }
+    } else if matches!(
+        loop_keyword(module_tokens),
+        Some(ReservedKind::While | ReservedKind::For)
+    ) {
+        // Loop statements end in a brace, so their `;` is optional
+        let statement = parse_loop_statement(module_tokens)?;
+        if next_token_is(
+            module_tokens,
+            TokenKind::Punctuation(PunctuationKind::Semicolon),
+        ) {
+            consume_next_token(module_tokens);
+        }
+        return Ok(BodyItem::Statement(statement));
+    } else if next_token_is(module_tokens, Reserved(ReservedKind::Break)) {
+        parse_break(module_tokens)?
+    } else if next_token_is(module_tokens, Reserved(ReservedKind::Continue)) {
+        // `continue;` or `continue 'label;`
+        let continue_tok = module_tokens.expect_token()?;
+        let label = match next_token_is(module_tokens, TokenKind::Label) {
+            true => Some(module_tokens.expect_token()?),
+            false => None,
+        };
+
+        let span = label
+            .as_ref()
+            .map_or(continue_tok.span, |label| continue_tok.span.to(label.span));
+        Statement::Continue { label, span }
+    } else if starts_binding(module_tokens) {
+        parse_binding(module_tokens)?
+    } else {
+        let expr = parse_expression(module_tokens)?;
+        if let Some(assign_kind) = module_tokens.peek_nth_kind(0)
+            && (assign_kind == Operator(OperatorKind::Assign)
+                || AssignOp::from_kind(assign_kind).is_some())
+        {
+            // `place = expr;` or `place += expr;`
+            consume_next_token(module_tokens);
+            let value = parse_expression(module_tokens)?;
+            if !expr.is_place() {
+                module_tokens.report(
+                    Diagnostic::error("invalid left-hand side of assignment")
+                        .with_code(codes::INVALID_ASSIGN_TARGET)
+                        .with_primary(expr.span(), "cannot assign to this expression")
+                        .with_note("only variables, fields and indexed elements can be assigned"),
+                );
+            }
+
+            match AssignOp::from_kind(assign_kind) {
+                Some(op) => Statement::CompoundAssign {
+                    target: expr,
+                    op,
+                    value,
+                },
+                None => Statement::Assign {
+                    target: expr,
+                    value,
+                },
+            }
+        } else if at_body_end(module_tokens) {
+            return Ok(BodyItem::Tail(expr));
+        } else if expr.is_block_like() {
+            // `if` and blocks end in a brace, so their `;` is optional
+            if next_token_is(
+                module_tokens,
+                TokenKind::Punctuation(PunctuationKind::Semicolon),
+            ) {
+                consume_next_token(module_tokens);
+            }
+            return Ok(BodyItem::Statement(Statement::Expr(expr)));
+        } else {
+            Statement::Expr(expr)
+        }
+    };
+
+    // A jump closing the body may omit its `;`
+    let is_jump = matches!(
+        statement,
+        Statement::Return { .. } | Statement::Break { .. } | Statement::Continue { .. }
+    );
+    if !(is_jump && at_body_end(module_tokens)) {
+        expect_semicolon(module_tokens, statement.span());
+    }
+
+    Ok(BodyItem::Statement(statement))
+}
+
+// `mut name ...`, `name :: type = ...` or `name := ...`
+fn starts_binding(module_tokens: &ModuleTokenStream<'_>) -> bool {
+    use OperatorKind::{InferAssign, TypeQualifier};
+
+    module_tokens.next_is(TokenKind::Reserved(ReservedKind::Mut))
+        || (module_tokens.next_is(TokenKind::Identifier)
+            && matches!(
+                module_tokens.peek_nth_kind(1),
+                Some(TokenKind::Operator(TypeQualifier | InferAssign))
+            ))
+}
+
+// Keyword of the loop starting at the cursor, looking past a label (`'outer :: while ...`)
+fn loop_keyword(module_tokens: &ModuleTokenStream<'_>) -> Option<ReservedKind> {
+    let labelled = module_tokens.next_is(TokenKind::Label);
+
+    match module_tokens.peek_nth_kind(if labelled { 2 } else { 0 }) {
+        Some(TokenKind::Reserved(
+            keyword @ (ReservedKind::While | ReservedKind::For | ReservedKind::Loop),
+        )) => Some(keyword),
+        _ => None,
+    }
+}
+
+// `'label ::` in front of a loop
+fn parse_loop_label(
+    module_tokens: &mut ModuleTokenStream<'_>,
+) -> Result<Option<Token>, Box<Diagnostic>> {
+    if !next_token_is(module_tokens, TokenKind::Label) {
+        return Ok(None);
+    }
+
+    let label = module_tokens.expect_token()?;
+    expect_next(
+        module_tokens,
+        &[TokenKind::Operator(OperatorKind::TypeQualifier)],
+    )?;
+    Ok(Some(label))
+}
+
+// `while cond { ... }` or `for var in start..end { ... }`, optionally labelled
+fn parse_loop_statement(
+    module_tokens: &mut ModuleTokenStream<'_>,
+) -> Result<Statement, Box<Diagnostic>> {
+    use TokenKind::{Identifier, Operator, Reserved};
+
+    let label = parse_loop_label(module_tokens)?;
+    let keyword = expect_next(
+        module_tokens,
+        &[Reserved(ReservedKind::While), Reserved(ReservedKind::For)],
+    )?;
+    let start_span = label.as_ref().map_or(keyword.span, |label| label.span);
+
+    if keyword.kind == Some(Reserved(ReservedKind::While)) {
+        let cond = parse_expression(module_tokens)?;
+        let body = parse_block(module_tokens)?;
+        let span = start_span.to(body.span);
+        return Ok(Statement::While {
+            label,
+            cond,
+            body,
+            span,
+        });
+    }
+
+    let var = expect_next(module_tokens, &[Identifier])?;
+    expect_next(module_tokens, &[Reserved(ReservedKind::In)])?;
+    let start = parse_expression(module_tokens)?;
+    expect_next(module_tokens, &[Operator(OperatorKind::Range)])?;
+    let end = parse_expression(module_tokens)?;
+    let body = parse_block(module_tokens)?;
+
+    let span = start_span.to(body.span);
+    Ok(Statement::For {
+        label,
+        var,
+        start: Box::new(start),
+        end: Box::new(end),
+        body,
+        span,
+    })
+}
+
+// `loop { ... }`, optionally labelled. Its value is given by `break value`.
+fn parse_loop_expr(
+    module_tokens: &mut ModuleTokenStream<'_>,
+) -> Result<Expression, Box<Diagnostic>> {
+    let label = parse_loop_label(module_tokens)?;
+    let loop_tok = expect_next(module_tokens, &[TokenKind::Reserved(ReservedKind::Loop)])?;
+    let body = parse_block(module_tokens)?;
+
+    let start_span = label.as_ref().map_or(loop_tok.span, |label| label.span);
+    let span = start_span.to(body.span);
+    Ok(Expression::LoopExpr { label, body, span })
+}
+
+// `break`, `break 'label`, `break value` or `break 'label value`
+fn parse_break(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Statement, Box<Diagnostic>> {
+    let break_tok = module_tokens.expect_token()?;
+
+    let label = match next_token_is(module_tokens, TokenKind::Label) {
+        true => Some(module_tokens.expect_token()?),
+        false => None,
+    };
+
+    let value = match next_token_is(
+        module_tokens,
+        TokenKind::Punctuation(PunctuationKind::Semicolon),
+    ) || at_body_end(module_tokens)
+    {
+        true => None,
+        false => Some(parse_expression(module_tokens)?),
+    };
+
+    let end = match (&value, &label) {
+        (Some(value), _) => value.span(),
+        (None, Some(label)) => label.span,
+        (None, None) => break_tok.span,
+    };
+    Ok(Statement::Break {
+        label,
+        value,
+        span: break_tok.span.to(end),
+    })
+}
+
+// Local binding with an explicit (`x :: s32 = 1`) or inferred (`x := 1`) type
+fn parse_binding(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Statement, Box<Diagnostic>> {
+    use OperatorKind::{Assign, InferAssign, TypeQualifier};
+    use TokenKind::Operator;
+
+    let mutability = match next_token_is(module_tokens, TokenKind::Reserved(ReservedKind::Mut)) {
+        true => {
+            consume_next_token(module_tokens);
+            Mutability::Mutable
+        }
+        false => Mutability::Immutable,
+    };
+
+    let name = expect_next(module_tokens, &[TokenKind::Identifier])?;
+
+    let qualifier = expect_next(
+        module_tokens,
+        &[Operator(TypeQualifier), Operator(InferAssign)],
+    )?;
+
+    let ty = match qualifier.kind {
+        Some(Operator(InferAssign)) => None,
+        _ => {
+            let ty = parse_type(module_tokens)?;
+            let _assign = expect_next(module_tokens, &[Operator(Assign)])?;
+            Some(ty)
+        }
+    };
+
+    let value = parse_expression(module_tokens)?;
+    Ok(Statement::Binding {
+        mutability,
+        name,
+        ty,
+        value,
+    })
+}
+
+// A missing `;` is reported, but parsing carries on as if it was there
+fn expect_semicolon(module_tokens: &mut ModuleTokenStream<'_>, statement_span: Span) {
+    use PunctuationKind::Semicolon;
+
+    if next_token_is(module_tokens, TokenKind::Punctuation(Semicolon)) {
+        consume_next_token(module_tokens);
+        return;
+    }
+
+    let found_span = module_tokens
+        .peek_token()
+        .map_or(module_tokens.eof_span(), |token| token.span);
+    let found = module_tokens
+        .peek_token()
+        .map_or("end of module".to_string(), |token| token.describe());
+    let statement_end = Span::new(statement_span.file, statement_span.end, statement_span.end);
+
+    module_tokens.report(
+        Diagnostic::error(format!("expected `;`, found {found}"))
+            .with_code(codes::MISSING_SEMICOLON)
+            .with_primary(found_span, "unexpected token")
+            .with_secondary(statement_span, "statement is not terminated")
+            .with_suggestion(statement_end, "add a semicolon", ";"),
+    );
+}
+
+/// Parse an expression. Operators bind from loosest to tightest as follows:
+///
+/// | Precedence | Operators                           | Associativity |
+/// |------------|-------------------------------------|---------------|
+/// | 1          | `or`                                | left          |
+/// | 2          | `and`                               | left          |
+/// | 3          | `==` `!=` `<` `<=` `>` `>=`         | none          |
+/// | 4          | `\|`                                | left          |
+/// | 5          | `^`                                 | left          |
+/// | 6          | `&`                                 | left          |
+/// | 7          | `<<` `>>`                           | left          |
+/// | 8          | `+` `-`                             | left          |
+/// | 9          | `*` `/` `%`                         | left          |
+/// | 10         | `as`                                | left          |
+/// | 11         | `-` `not` `!` `~` (prefix)          | right         |
+/// | 12         | `f(a, b)` `a.b` `a[i]` (postfix)    | left          |
+///
+/// Comparisons do not chain: `a < b < c` is reported as an error.
+fn parse_expression(
+    module_tokens: &mut ModuleTokenStream<'_>,
+) -> Result<Expression, Box<Diagnostic>> {
+    parse_expression_bp(module_tokens, 0)
+}
+
+// Binding power of `as`, prefix and postfix operators. Higher binds tighter.
+const AS_BP: u8 = 19;
+const PREFIX_BP: u8 = 21;
+const POSTFIX_BP: u8 = 23;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+}
+
+impl BinaryOp {
+    fn from_kind(kind: TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::Reserved(ReservedKind::Or) => Some(BinaryOp::Or),
+            TokenKind::Reserved(ReservedKind::And) => Some(BinaryOp::And),
+            TokenKind::Operator(OperatorKind::Eq) => Some(BinaryOp::Eq),
+            TokenKind::Operator(OperatorKind::NotEq) => Some(BinaryOp::Ne),
+            TokenKind::Operator(OperatorKind::LT) => Some(BinaryOp::Lt),
+            TokenKind::Operator(OperatorKind::LTE) => Some(BinaryOp::Lte),
+            TokenKind::Operator(OperatorKind::GT) => Some(BinaryOp::Gt),
+            TokenKind::Operator(OperatorKind::GTE) => Some(BinaryOp::Gte),
+            TokenKind::Operator(OperatorKind::BitOr) => Some(BinaryOp::BitOr),
+            TokenKind::Operator(OperatorKind::BitXor) => Some(BinaryOp::BitXor),
+            TokenKind::Operator(OperatorKind::BitAnd) => Some(BinaryOp::BitAnd),
+            TokenKind::Operator(OperatorKind::Shl) => Some(BinaryOp::Shl),
+            TokenKind::Operator(OperatorKind::Shr) => Some(BinaryOp::Shr),
+            TokenKind::Operator(OperatorKind::Plus) => Some(BinaryOp::Add),
+            TokenKind::Operator(OperatorKind::Sub) => Some(BinaryOp::Sub),
+            TokenKind::Operator(OperatorKind::Mul) => Some(BinaryOp::Mul),
+            TokenKind::Operator(OperatorKind::Div) => Some(BinaryOp::Div),
+            TokenKind::Operator(OperatorKind::Mod) => Some(BinaryOp::Mod),
+            _ => None,
+        }
+    }
+
+    // Left and right binding power. Left associative operators bind tighter to
+    // their right, so `a - b - c` is parsed as `(a - b) - c`.
+    fn binding_power(self) -> (u8, u8) {
+        match self {
+            BinaryOp::Or => (1, 2),
+            BinaryOp::And => (3, 4),
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Lte
+            | BinaryOp::Gt
+            | BinaryOp::Gte => (5, 6),
+            BinaryOp::BitOr => (7, 8),
+            BinaryOp::BitXor => (9, 10),
+            BinaryOp::BitAnd => (11, 12),
+            BinaryOp::Shl | BinaryOp::Shr => (13, 14),
+            BinaryOp::Add | BinaryOp::Sub => (15, 16),
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => (17, 18),
+        }
+    }
+
+    fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Lte
+                | BinaryOp::Gt
+                | BinaryOp::Gte
+        )
+    }
+
+    fn build(self, lhs: Expression, rhs: Expression) -> Expression {
+        let (lhs, rhs) = (Box::new(lhs), Box::new(rhs));
+        match self {
+            BinaryOp::Or => Expression::OrExpr(lhs, rhs),
+            BinaryOp::And => Expression::AndExpr(lhs, rhs),
+            BinaryOp::Eq => Expression::EqExpr(lhs, rhs),
+            BinaryOp::Ne => Expression::NeExpr(lhs, rhs),
+            BinaryOp::Lt => Expression::LtExpr(lhs, rhs),
+            BinaryOp::Lte => Expression::LteExpr(lhs, rhs),
+            BinaryOp::Gt => Expression::GtExpr(lhs, rhs),
+            BinaryOp::Gte => Expression::GteExpr(lhs, rhs),
+            BinaryOp::BitOr => Expression::BitOrExpr(lhs, rhs),
+            BinaryOp::BitXor => Expression::BitXorExpr(lhs, rhs),
+            BinaryOp::BitAnd => Expression::BitAndExpr(lhs, rhs),
+            BinaryOp::Shl => Expression::ShlExpr(lhs, rhs),
+            BinaryOp::Shr => Expression::ShrExpr(lhs, rhs),
+            BinaryOp::Add => Expression::AddExpr(lhs, rhs),
+            BinaryOp::Sub => Expression::SubExpr(lhs, rhs),
+            BinaryOp::Mul => Expression::MulExpr(lhs, rhs),
+            BinaryOp::Div => Expression::DivExpr(lhs, rhs),
+            BinaryOp::Mod => Expression::ModExpr(lhs, rhs),
+        }
+    }
+}
+
+// Parse an expression whose operators bind at least as tight as `min_bp`
+fn parse_expression_bp(
+    module_tokens: &mut ModuleTokenStream<'_>,
+    min_bp: u8,
+) -> Result<Expression, Box<Diagnostic>> {
+    use PunctuationKind::{Dot, OpenBracket, OpenParen};
+
+    let mut lhs = parse_prefix(module_tokens)?;
+
+    while let Some(kind) = module_tokens.peek_nth_kind(0) {
+        if matches!(kind, TokenKind::Punctuation(OpenParen | OpenBracket | Dot)) {
+            if POSTFIX_BP < min_bp {
+                break;
+            }
+
+            lhs = parse_postfix(module_tokens, lhs)?;
+            continue;
         }
 
-        // Check for closing brace
-        if next_token_is(module_tokens, Punctuation(CloseBrace)) {
-            // Note that we do not consume the token here.
-            // Instead, we consume it in the `parse_declarations()` function further
-            // the call stack.
+        // `expr as type`
+        if kind == TokenKind::Reserved(ReservedKind::As) {
+            if AS_BP < min_bp {
+                break;
+            }
+
+            let as_tok = module_tokens.expect_token()?;
+            let ty = parse_type(module_tokens)?;
+            let span = lhs.span().to(ty.span().unwrap_or(as_tok.span));
+            lhs = Expression::CastExpr {
+                expr: Box::new(lhs),
+                ty,
+                span,
+            };
+            continue;
+        }
+
+        let Some(op) = BinaryOp::from_kind(kind) else {
             break;
+        };
+
+        let (l_bp, r_bp) = op.binding_power();
+        if l_bp < min_bp {
+            break;
+        }
+
+        // Report chained comparisons, but parse them left associative to carry on
+        let op_tok = module_tokens.expect_token()?;
+        if op.is_comparison() && lhs.is_comparison() {
+            module_tokens.report(
+                Diagnostic::error("comparison operators cannot be chained")
+                    .with_code(codes::CHAINED_COMPARISON)
+                    .with_primary(op_tok.span, "second comparison")
+                    .with_secondary(lhs.span(), "first comparison")
+                    .with_help("combine the comparisons with `and` (e.g. `a < b and b < c`)"),
+            );
         }
+
+        let rhs = parse_expression_bp(module_tokens, r_bp)?;
+        lhs = op.build(lhs, rhs);
     }
 
-    DeclarationDef::Struct { fields }
+    Ok(lhs)
+}
+
+// `-operand`, `not operand`, `!operand`, `~operand` or an atom
+fn parse_prefix(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Expression, Box<Diagnostic>> {
+    match module_tokens.peek_nth_kind(0) {
+        Some(TokenKind::Operator(OperatorKind::Sub)) => {
+            let op_tok = module_tokens.expect_token()?;
+            let operand = parse_expression_bp(module_tokens, PREFIX_BP)?;
+            let span = op_tok.span.to(operand.span());
+            Ok(Expression::NegExpr(Box::new(operand), span))
+        }
+        Some(TokenKind::Reserved(ReservedKind::Not) | TokenKind::Operator(OperatorKind::Not)) => {
+            let op_tok = module_tokens.expect_token()?;
+            let operand = parse_expression_bp(module_tokens, PREFIX_BP)?;
+            let span = op_tok.span.to(operand.span());
+            Ok(Expression::NotExpr(Box::new(operand), span))
+        }
+        Some(TokenKind::Operator(OperatorKind::BitNot)) => {
+            let op_tok = module_tokens.expect_token()?;
+            let operand = parse_expression_bp(module_tokens, PREFIX_BP)?;
+            let span = op_tok.span.to(operand.span());
+            Ok(Expression::BitNotExpr(Box::new(operand), span))
+        }
+        _ => parse_atom(module_tokens),
+    }
+}
+
+// `base(args)`, `base[index]` or `base.field`
+fn parse_postfix(
+    module_tokens: &mut ModuleTokenStream<'_>,
+    base: Expression,
+) -> Result<Expression, Box<Diagnostic>> {
+    use PunctuationKind::{CloseBracket, CloseParen, Comma, OpenBracket, OpenParen};
+    use TokenKind::Punctuation;
+
+    let op_tok = module_tokens.expect_token()?;
+    match op_tok.kind {
+        Some(Punctuation(OpenParen)) => {
+            let mut args: Vec<Expression> = Vec::new();
+            while !next_token_is(module_tokens, Punctuation(CloseParen)) {
+                args.push(parse_expression(module_tokens)?);
+
+                // Arguments are separated by commas, a trailing one is allowed
+                if !next_token_is(module_tokens, Punctuation(Comma)) {
+                    break;
+                }
+                consume_next_token(module_tokens);
+            }
+
+            let r_paren = expect_next(module_tokens, &[Punctuation(CloseParen)])?;
+            let span = base.span().to(r_paren.span);
+            Ok(Expression::CallExpr(Box::new(base), args, span))
+        }
+        Some(Punctuation(OpenBracket)) => {
+            let index = parse_expression(module_tokens)?;
+            let r_bracket = expect_next(module_tokens, &[Punctuation(CloseBracket)])?;
+            let span = base.span().to(r_bracket.span);
+            Ok(Expression::IndexExpr(Box::new(base), Box::new(index), span))
+        }
+        _ => {
+            let field = expect_next(module_tokens, &[TokenKind::Identifier])?;
+            Ok(Expression::FieldExpr(Box::new(base), field))
+        }
+    }
+}
+
+// `{ statements... tail }`
+fn parse_block(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Block, Box<Diagnostic>> {
+    use PunctuationKind::{CloseBrace, OpenBrace};
+    use TokenKind::Punctuation;
+
+    let l_brace = expect_next(module_tokens, &[Punctuation(OpenBrace)])?;
+    let (body, expr) = parse_body_items(module_tokens);
+    let r_brace = expect_next(module_tokens, &[Punctuation(CloseBrace)])?;
+
+    Ok(Block {
+        body,
+        expr: expr.map(Box::new),
+        span: l_brace.span.to(r_brace.span),
+    })
+}
+
+// `if cond { ... }`, optionally followed by `else { ... }` or `else if ...`
+fn parse_if(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Expression, Box<Diagnostic>> {
+    use TokenKind::{Punctuation, Reserved};
+
+    let if_tok = expect_next(module_tokens, &[Reserved(ReservedKind::If)])?;
+
+    // Otherwise the block would be taken for the condition
+    if next_token_is(module_tokens, Punctuation(PunctuationKind::OpenBrace)) {
+        return Err(Box::new(
+            Diagnostic::error("missing condition in `if` expression")
+                .with_code(codes::UNEXPECTED_TOKEN)
+                .with_primary(if_tok.span, "expected a condition after this"),
+        ));
+    }
+
+    let cond = parse_expression(module_tokens)?;
+    let then_branch = parse_block(module_tokens)?;
+
+    let else_branch = match next_token_is(module_tokens, Reserved(ReservedKind::Else)) {
+        true => {
+            consume_next_token(module_tokens);
+            let else_branch = match next_token_is(module_tokens, Reserved(ReservedKind::If)) {
+                true => parse_if(module_tokens)?,
+                false => {
+                    // Report a missing brace at the token after `else`
+                    if !next_token_is(module_tokens, Punctuation(PunctuationKind::OpenBrace)) {
+                        expect_next(
+                            module_tokens,
+                            &[
+                                Punctuation(PunctuationKind::OpenBrace),
+                                Reserved(ReservedKind::If),
+                            ],
+                        )?;
+                    }
+                    Expression::BlockExpr(parse_block(module_tokens)?)
+                }
+            };
+            Some(Box::new(else_branch))
+        }
+        false => None,
+    };
+
+    let end = else_branch
+        .as_ref()
+        .map_or(then_branch.span, |else_branch| else_branch.span());
+    Ok(Expression::IfExpr {
+        cond: Box::new(cond),
+        then_branch,
+        else_branch,
+        span: if_tok.span.to(end),
+    })
+}
+
+fn parse_atom(module_tokens: &mut ModuleTokenStream<'_>) -> Result<Expression, Box<Diagnostic>> {
+    use PunctuationKind::{CloseParen, OpenBrace, OpenParen};
+
+    // Only peek, so that a closing brace ending the body early is not eaten
+    let Some(token) = module_tokens.peek_token() else {
+        return Err(Box::new(module_tokens.eof_error()));
+    };
+
+    match token.kind {
+        Some(
+            TokenKind::IntLiteral
+            | TokenKind::FloatLiteral
+            | TokenKind::StringLiteral
+            | TokenKind::CharLiteral
+            | TokenKind::BoolLiteral,
+        ) => {
+            consume_next_token(module_tokens);
+            Ok(Expression::Atom {
+                inner: Atom::Literal(token),
+            })
+        }
+        // Labelled loop (`'outer :: loop { ... }`)
+        Some(TokenKind::Label) if loop_keyword(module_tokens) == Some(ReservedKind::Loop) => {
+            parse_loop_expr(module_tokens)
+        }
+        Some(TokenKind::Identifier) => {
+            consume_next_token(module_tokens);
+            Ok(Expression::Atom {
+                inner: Atom::Identifier(token),
+            })
+        }
+        Some(TokenKind::Reserved(ReservedKind::If)) => parse_if(module_tokens),
+        Some(TokenKind::Reserved(ReservedKind::Loop)) => parse_loop_expr(module_tokens),
+        Some(TokenKind::Punctuation(OpenBrace)) => {
+            Ok(Expression::BlockExpr(parse_block(module_tokens)?))
+        }
+        Some(TokenKind::Punctuation(OpenParen)) => {
+            consume_next_token(module_tokens);
+            let expr = parse_expression(module_tokens)?;
+            let r_paren = expect_next(module_tokens, &[TokenKind::Punctuation(CloseParen)])?;
+            Ok(Expression::ParenExpr(
+                Box::new(expr),
+                token.span.to(r_paren.span),
+            ))
+        }
+        _ => Err(Box::new(
+            Diagnostic::error(format!(
+                "expected an expression, found {}",
+                token.describe()
+            ))
+            .with_code(codes::UNEXPECTED_TOKEN)
+            .with_primary(token.span, "expected an expression"),
+        )),
+    }
+}
+
+/// `import util.math;` makes the items of module `util.math` visible.
+#[derive(Debug)]
+pub struct Import {
+    pub path: Vec<Token>,
+    pub span: Span,
+}
+
+impl Import {
+    /// Dotted module name (e.g. `util.math`).
+    pub fn name(&self) -> String {
+        self.path
+            .iter()
+            .map(|segment| segment.content.as_str())
+            .collect::<Vec<&str>>()
+            .join(".")
+    }
+
+    /// Span of the module name, without `import` and `;`.
+    pub fn path_span(&self) -> Span {
+        let first = self.path.first().expect("Imports name at least one module");
+        let last = self.path.last().expect("Imports name at least one module");
+        first.span.to(last.span)
+    }
 }
 
 #[derive(Debug)]
-struct Declaration {
-    sig: DeclarationSignature,
-    def: DeclarationDef,
+pub struct Declaration {
+    pub vis: Visibility,
+    pub sig: DeclarationSignature,
+    pub def: DeclarationDef,
 }
 
 #[derive(Debug)]
-struct DeclarationSignature {
-    name: Token,
-    ty: Type,
+pub struct DeclarationSignature {
+    pub name: Token,
+    pub ty: TypeExpr,
 }
 
 impl DeclarationSignature {
-    fn new(name: Token, ty: Type) -> Self {
+    fn new(name: Token, ty: TypeExpr) -> Self {
         Self { name, ty }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    // Only visible inside the declaring module (the default)
+    Private,
+
+    // Marked `pub`: also visible to modules importing the declaring module
+    Public,
+}
+
+/// A type as written in the source. Names are resolved by the typer.
+#[derive(Debug, Clone)]
+pub enum TypeExpr {
+    // Primitive type keyword (e.g. `s32`)
+    Prim(Token),
+
+    // User-defined type referred to by name
+    Named(Token),
+    Struct,
+    Enum,
+    Proc {
+        params: Vec<Param>,
+        output: Option<Box<TypeExpr>>,
+    },
+}
+
+impl TypeExpr {
+    /// Span of a type referred to by a single token (e.g. `s32` or `Point`).
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            TypeExpr::Prim(token) | TypeExpr::Named(token) => Some(token.span),
+            TypeExpr::Struct | TypeExpr::Enum | TypeExpr::Proc { .. } => None,
+        }
+    }
+}
+
 #[derive(Debug)]
-enum DeclarationDef {
+pub enum DeclarationDef {
     Struct { fields: Vec<Field> },
     Enum { variants: Vec<Variant> },
     Function { def: FunctionDef },
@@ -250,49 +1304,831 @@ enum DeclarationDef {
 }
 
 #[derive(Debug)]
-struct Variant {
-    name: Token,
+pub struct Variant {
+    pub vis: Visibility,
+    pub name: Token,
 }
 
 #[derive(Debug)]
-struct Field {
-    name: Token,
-    ty: Type,
+pub struct Field {
+    pub vis: Visibility,
+    pub name: Token,
+    pub ty: TypeExpr,
 }
 impl Field {
-    fn new(name: Token, ty: Type) -> Self {
-        Self { name, ty }
+    fn new(vis: Visibility, name: Token, ty: TypeExpr) -> Self {
+        Self { vis, name, ty }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: Token,
+    pub ty: TypeExpr,
+}
+
 #[derive(Debug)]
-struct Param {
-    name: Token,
-    ty: Type,
+pub struct FunctionDef {
+    pub body: Vec<Statement>,
+    pub expr: Option<Expression>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutability {
+    // Bound once (the default)
+    Immutable,
+
+    // Declared with `mut`: may be assigned to
+    Mutable,
+}
+
+/// Operator of a compound assignment (e.g. `+` in `x += 1`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssignOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl AssignOp {
+    fn from_kind(kind: TokenKind) -> Option<Self> {
+        match kind {
+            TokenKind::Operator(OperatorKind::AssignPlus) => Some(AssignOp::Add),
+            TokenKind::Operator(OperatorKind::AssignSub) => Some(AssignOp::Sub),
+            TokenKind::Operator(OperatorKind::AssignMul) => Some(AssignOp::Mul),
+            TokenKind::Operator(OperatorKind::AssignDiv) => Some(AssignOp::Div),
+            TokenKind::Operator(OperatorKind::AssignMod) => Some(AssignOp::Mod),
+            TokenKind::Operator(OperatorKind::AssignBitAnd) => Some(AssignOp::BitAnd),
+            TokenKind::Operator(OperatorKind::AssignBitOr) => Some(AssignOp::BitOr),
+            TokenKind::Operator(OperatorKind::AssignBitXor) => Some(AssignOp::BitXor),
+            TokenKind::Operator(OperatorKind::AssignShl) => Some(AssignOp::Shl),
+            TokenKind::Operator(OperatorKind::AssignShr) => Some(AssignOp::Shr),
+            _ => None,
+        }
+    }
+}
+
+/// Braced sequence of statements. Its value is that of the tail expression, if any.
 #[derive(Debug)]
-struct FunctionDef {
-    body: Vec<Statement>,
-    expr: Option<Expression>,
+pub struct Block {
+    pub body: Vec<Statement>,
+    pub expr: Option<Box<Expression>>,
+    pub span: Span,
 }
 
 #[derive(Debug)]
-enum Statement {
-    Assign,
+pub enum Statement {
+    // `name :: type = value;` or `name := value;`, optionally preceded by `mut`
+    Binding {
+        mutability: Mutability,
+        name: Token,
+        ty: Option<TypeExpr>,
+        value: Expression,
+    },
+
+    // `place = value;`
+    Assign {
+        target: Expression,
+        value: Expression,
+    },
+
+    // `place op= value;` for `+=` `-=` `*=` `/=` `%=` `&=` `|=` `^=` `<<=` `>>=`
+    CompoundAssign {
+        target: Expression,
+        op: AssignOp,
+        value: Expression,
+    },
+
+    // Expression evaluated for its side effects (e.g. `work();`)
+    Expr(Expression),
+
+    // `return;` or `return value;`
+    Return {
+        value: Option<Expression>,
+        span: Span,
+    },
+
+    // `while cond { ... }`, optionally labelled (`'outer :: while ...`)
+    While {
+        label: Option<Token>,
+        cond: Expression,
+        body: Block,
+        span: Span,
+    },
+
+    // `for var in start..end { ... }`, counting up from `start` to `end` (exclusive)
+    For {
+        label: Option<Token>,
+        var: Token,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        body: Block,
+        span: Span,
+    },
+
+    // `break;`, `break 'label;`, `break value;` or `break 'label value;`
+    Break {
+        label: Option<Token>,
+        value: Option<Expression>,
+        span: Span,
+    },
+
+    // `continue;` or `continue 'label;`
+    Continue {
+        label: Option<Token>,
+        span: Span,
+    },
 }
 
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Binding { name, value, .. } => name.span.to(value.span()),
+            Statement::Assign { target, value }
+            | Statement::CompoundAssign { target, value, .. } => target.span().to(value.span()),
+            Statement::Expr(expr) => expr.span(),
+            Statement::Return { span, .. }
+            | Statement::While { span, .. }
+            | Statement::For { span, .. }
+            | Statement::Break { span, .. }
+            | Statement::Continue { span, .. } => *span,
+        }
+    }
+}
+
+/// Expression tree built by `parse_expression()`. Binary operators hold their
+/// operands; unary and postfix operators also keep the span they cover.
 #[derive(Debug)]
-enum Expression {
+pub enum Expression {
     AddExpr(Box<Expression>, Box<Expression>),
     SubExpr(Box<Expression>, Box<Expression>),
     MulExpr(Box<Expression>, Box<Expression>),
     DivExpr(Box<Expression>, Box<Expression>),
-    Atom { inner: Atom },
+    ModExpr(Box<Expression>, Box<Expression>),
+    LtExpr(Box<Expression>, Box<Expression>),
+    LteExpr(Box<Expression>, Box<Expression>),
+    GtExpr(Box<Expression>, Box<Expression>),
+    GteExpr(Box<Expression>, Box<Expression>),
+    EqExpr(Box<Expression>, Box<Expression>),
+    NeExpr(Box<Expression>, Box<Expression>),
+
+    // `lhs and rhs`, `lhs or rhs`. `rhs` is only evaluated if `lhs` does not
+    // already decide the result.
+    AndExpr(Box<Expression>, Box<Expression>),
+    OrExpr(Box<Expression>, Box<Expression>),
+
+    BitAndExpr(Box<Expression>, Box<Expression>),
+    BitOrExpr(Box<Expression>, Box<Expression>),
+    BitXorExpr(Box<Expression>, Box<Expression>),
+    ShlExpr(Box<Expression>, Box<Expression>),
+    ShrExpr(Box<Expression>, Box<Expression>),
+
+    // `-operand`
+    NegExpr(Box<Expression>, Span),
+
+    // `not operand` or `!operand`
+    NotExpr(Box<Expression>, Span),
+
+    // `~operand`
+    BitNotExpr(Box<Expression>, Span),
+
+    // `expr as type`
+    CastExpr {
+        expr: Box<Expression>,
+        ty: TypeExpr,
+        span: Span,
+    },
+
+    // `callee(args)`
+    CallExpr(Box<Expression>, Vec<Expression>, Span),
+
+    // `base.field`
+    FieldExpr(Box<Expression>, Token),
+
+    // `base[index]`
+    IndexExpr(Box<Expression>, Box<Expression>, Span),
+
+    // Parenthesised expression, kept for its span
+    ParenExpr(Box<Expression>, Span),
+
+    // `if cond { ... } else ...`. The else branch is a `BlockExpr` or another `IfExpr`.
+    IfExpr {
+        cond: Box<Expression>,
+        then_branch: Block,
+        else_branch: Option<Box<Expression>>,
+        span: Span,
+    },
+
+    // `{ ... }`
+    BlockExpr(Block),
+
+    // `loop { ... }` or `'label :: loop { ... }`
+    LoopExpr {
+        label: Option<Token>,
+        body: Block,
+        span: Span,
+    },
+    Atom {
+        inner: Atom,
+    },
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::AddExpr(lhs, rhs)
+            | Expression::SubExpr(lhs, rhs)
+            | Expression::MulExpr(lhs, rhs)
+            | Expression::DivExpr(lhs, rhs)
+            | Expression::ModExpr(lhs, rhs)
+            | Expression::LtExpr(lhs, rhs)
+            | Expression::LteExpr(lhs, rhs)
+            | Expression::GtExpr(lhs, rhs)
+            | Expression::GteExpr(lhs, rhs)
+            | Expression::EqExpr(lhs, rhs)
+            | Expression::NeExpr(lhs, rhs)
+            | Expression::AndExpr(lhs, rhs)
+            | Expression::OrExpr(lhs, rhs)
+            | Expression::BitAndExpr(lhs, rhs)
+            | Expression::BitOrExpr(lhs, rhs)
+            | Expression::BitXorExpr(lhs, rhs)
+            | Expression::ShlExpr(lhs, rhs)
+            | Expression::ShrExpr(lhs, rhs) => lhs.span().to(rhs.span()),
+            Expression::NegExpr(_, span)
+            | Expression::NotExpr(_, span)
+            | Expression::BitNotExpr(_, span)
+            | Expression::CallExpr(_, _, span)
+            | Expression::IndexExpr(_, _, span)
+            | Expression::ParenExpr(_, span)
+            | Expression::CastExpr { span, .. }
+            | Expression::IfExpr { span, .. }
+            | Expression::LoopExpr { span, .. } => *span,
+            Expression::BlockExpr(block) => block.span,
+            Expression::FieldExpr(base, field) => base.span().to(field.span),
+            Expression::Atom { inner } => inner.span(),
+        }
+    }
+
+    fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Expression::LtExpr(..)
+                | Expression::LteExpr(..)
+                | Expression::GtExpr(..)
+                | Expression::GteExpr(..)
+                | Expression::EqExpr(..)
+                | Expression::NeExpr(..)
+        )
+    }
+
+    /// Whether the expression ends in a block, so that it can stand as a
+    /// statement without a `;`.
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            Expression::IfExpr { .. } | Expression::BlockExpr(_) | Expression::LoopExpr { .. }
+        )
+    }
+
+    /// Whether the expression denotes a memory location that can be assigned to.
+    pub fn is_place(&self) -> bool {
+        match self {
+            Expression::Atom {
+                inner: Atom::Identifier(_),
+            }
+            | Expression::FieldExpr(..)
+            | Expression::IndexExpr(..) => true,
+            Expression::ParenExpr(inner, _) => inner.is_place(),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug)]
-enum Atom {
-    Literal,
-    Identifier,
+pub enum Atom {
+    Literal(Token),
+    Identifier(Token),
+}
+
+impl Atom {
+    pub fn span(&self) -> Span {
+        match self {
+            Atom::Literal(token) | Atom::Identifier(token) => token.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use lexical_analyzer::Scanner;
+    use module_manager::ModuleManager;
+
+    use super::*;
+
+    // Parse `src` as a single module. Returns the AST and every diagnostic as
+    // its message and the source text under its primary label.
+    fn parse(src: &str) -> (Ast, Vec<(String, String)>) {
+        let module_manager =
+            ModuleManager::from_sources([(PathBuf::from("main.gem"), src.to_string())]);
+        let (token_streams, scanner_errors) = Scanner::new(&module_manager).scan();
+        assert!(scanner_errors.is_empty(), "{scanner_errors:?}");
+
+        let (ast, diagnostics) = Ast::new(token_streams);
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let span = diagnostic
+                    .primary_span()
+                    .expect("Errors point at the source");
+                (
+                    diagnostic.message.clone(),
+                    module_manager.source_map().snippet(span).to_string(),
+                )
+            })
+            .collect();
+
+        (ast, diagnostics)
+    }
+
+    fn names<'a>(tokens: impl IntoIterator<Item = &'a Token>) -> Vec<&'a str> {
+        tokens
+            .into_iter()
+            .map(|token| token.content.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn every_mistake_is_reported_once() {
+        let (ast, diagnostics) = parse(
+            "Point :: struct { x :: s32, y s32, z :: }
+            Color :: enum { Red, 3, Green }
+            f :: proc (a :: s32, b) -> s32 {
+                x := a +;
+                y := 1
+                a
+            }
+            g :: proc () -> s32 { f(1, 2) }",
+        );
+
+        let expected = [
+            ("expected `::`, found `s32`", "s32"),
+            ("expected a type, found `}`", "}"),
+            ("expected identifier, found integer literal `3`", "3"),
+            ("expected `::`, found `)`", ")"),
+            ("expected an expression, found `;`", ";"),
+            ("expected `;`, found identifier `a`", "a"),
+        ];
+        let expected = expected
+            .iter()
+            .map(|&(message, snippet)| (message.to_string(), snippet.to_string()))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(diagnostics, expected);
+
+        // Declarations after each mistake are still parsed
+        let declarations = &ast.program.modules[0].declarations;
+        assert_eq!(
+            names(declarations.iter().map(|decl| &decl.sig.name)),
+            ["Point", "Color", "f", "g"]
+        );
+
+        let DeclarationDef::Struct { fields } = &declarations[0].def else {
+            panic!("`Point` is a struct");
+        };
+        assert_eq!(names(fields.iter().map(|field| &field.name)), ["x"]);
+
+        let DeclarationDef::Enum { variants } = &declarations[1].def else {
+            panic!("`Color` is an enum");
+        };
+        assert_eq!(
+            names(variants.iter().map(|variant| &variant.name)),
+            ["Red", "Green"]
+        );
+
+        let TypeExpr::Proc { params, output } = &declarations[2].sig.ty else {
+            panic!("`f` is a proc");
+        };
+        assert_eq!(names(params.iter().map(|param| &param.name)), ["a"]);
+        assert!(output.is_some());
+
+        let DeclarationDef::Function { def } = &declarations[2].def else {
+            panic!("`f` is a proc");
+        };
+        assert!(matches!(
+            def.body.as_slice(),
+            [Statement::Binding { name, .. }] if name.content == "y"
+        ));
+        assert!(def.expr.is_some());
+
+        let DeclarationDef::Function { def } = &declarations[3].def else {
+            panic!("`g` is a proc");
+        };
+        assert!(matches!(def.expr, Some(Expression::CallExpr(_, ref args, _)) if args.len() == 2));
+    }
+
+    #[test]
+    fn mismatched_brackets_are_reported_once() {
+        let (ast, diagnostics) = parse(
+            "Color :: enum { Red ) Green }
+            f :: proc (a :: { a }
+            g :: proc (a :: s32 ] { a + ]; }
+            )
+            h :: proc () { work(a b) + 1; x := [1; }
+            k :: proc () {}",
+        );
+
+        let messages = diagnostics
+            .iter()
+            .map(|(message, _)| message.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            messages,
+            [
+                "expected identifier, found `)`",
+                "expected a type, found `{`",
+                "expected `)`, found `]`",
+                "expected an expression, found `]`",
+                "expected identifier or `main`, found `)`",
+                "expected `)`, found identifier `b`",
+                "expected an expression, found `[`",
+            ]
+        );
+
+        let declarations = &ast.program.modules[0].declarations;
+        assert_eq!(
+            names(declarations.iter().map(|decl| &decl.sig.name)),
+            ["Color", "f", "g", "h", "k"]
+        );
+    }
+
+    #[test]
+    fn pub_marks_declarations_fields_and_variants() {
+        let (ast, diagnostics) = parse(
+            "pub Point :: struct { pub x :: s32, y :: s32 }
+            Color :: enum { Red, pub Green }
+            pub f :: proc () {}
+            g :: proc () {}",
+        );
+        assert_eq!(diagnostics, []);
+
+        let declarations = &ast.program.modules[0].declarations;
+        let vis = declarations
+            .iter()
+            .map(|decl| (decl.sig.name.content.as_str(), decl.vis))
+            .collect::<Vec<(&str, Visibility)>>();
+        assert_eq!(
+            vis,
+            [
+                ("Point", Visibility::Public),
+                ("Color", Visibility::Private),
+                ("f", Visibility::Public),
+                ("g", Visibility::Private),
+            ]
+        );
+
+        let DeclarationDef::Struct { fields } = &declarations[0].def else {
+            panic!("`Point` is a struct");
+        };
+        let fields = fields
+            .iter()
+            .map(|field| (field.name.content.as_str(), field.vis))
+            .collect::<Vec<(&str, Visibility)>>();
+        assert_eq!(
+            fields,
+            [("x", Visibility::Public), ("y", Visibility::Private)]
+        );
+
+        let DeclarationDef::Enum { variants } = &declarations[1].def else {
+            panic!("`Color` is an enum");
+        };
+        let variants = variants
+            .iter()
+            .map(|variant| (variant.name.content.as_str(), variant.vis))
+            .collect::<Vec<(&str, Visibility)>>();
+        assert_eq!(
+            variants,
+            [("Red", Visibility::Private), ("Green", Visibility::Public)]
+        );
+    }
+
+    #[test]
+    fn pub_must_precede_a_name() {
+        let (_, diagnostics) = parse(
+            "pub pub f :: proc () {}
+            Point :: struct { pub pub x :: s32 }
+            main :: proc () { pub x := 1; }",
+        );
+        let expected = |message: &str| (message.to_string(), "pub".to_string());
+        assert_eq!(
+            diagnostics,
+            [
+                expected("expected identifier or `main`, found `pub`"),
+                expected("expected identifier, found `pub`"),
+                // Locals are never visible outside their proc
+                expected("expected an expression, found `pub`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn proc_bodies_hold_statements_and_a_tail() {
+        let (ast, diagnostics) = parse(
+            "f :: proc (a :: s32) -> s32 {
+                b := a;
+                work(b);
+                return b;
+            }
+            g :: proc () -> s32 { work(); 1 + 2 }
+            h :: proc () { return }
+            k :: proc () {}",
+        );
+        assert_eq!(diagnostics, []);
+
+        let bodies = ast.program.modules[0]
+            .declarations
+            .iter()
+            .map(|decl| match &decl.def {
+                DeclarationDef::Function { def } => def,
+                _ => panic!("Every declaration is a proc"),
+            })
+            .collect::<Vec<&FunctionDef>>();
+
+        assert!(matches!(
+            bodies[0].body.as_slice(),
+            [
+                Statement::Binding { .. },
+                Statement::Expr(Expression::CallExpr(..)),
+                Statement::Return { value: Some(_), .. },
+            ]
+        ));
+        assert!(bodies[0].expr.is_none());
+
+        assert!(matches!(
+            bodies[1].body.as_slice(),
+            [Statement::Expr(Expression::CallExpr(..))]
+        ));
+        assert!(matches!(bodies[1].expr, Some(Expression::AddExpr(..))));
+
+        assert!(matches!(
+            bodies[2].body.as_slice(),
+            [Statement::Return { value: None, .. }]
+        ));
+        assert!(bodies[3].body.is_empty() && bodies[3].expr.is_none());
+    }
+
+    // Expression bound by the only statement of the only proc, as an
+    // S-expression (e.g. `(+ a (* b c))`)
+    fn parse_expr(src: &str) -> (String, Vec<(String, String)>) {
+        let (ast, diagnostics) = parse(&format!("f :: proc () {{ x := {src}; }}"));
+        let DeclarationDef::Function { def } = &ast.program.modules[0].declarations[0].def else {
+            panic!("`f` is a proc");
+        };
+        let [Statement::Binding { value, .. }] = def.body.as_slice() else {
+            panic!("`f` holds a single binding");
+        };
+        (sexp(value), diagnostics)
+    }
+
+    fn sexp(expr: &Expression) -> String {
+        let binary = |op: &str, lhs: &Expression, rhs: &Expression| {
+            format!("({op} {} {})", sexp(lhs), sexp(rhs))
+        };
+        match expr {
+            Expression::AddExpr(lhs, rhs) => binary("+", lhs, rhs),
+            Expression::SubExpr(lhs, rhs) => binary("-", lhs, rhs),
+            Expression::MulExpr(lhs, rhs) => binary("*", lhs, rhs),
+            Expression::DivExpr(lhs, rhs) => binary("/", lhs, rhs),
+            Expression::ModExpr(lhs, rhs) => binary("%", lhs, rhs),
+            Expression::LtExpr(lhs, rhs) => binary("<", lhs, rhs),
+            Expression::LteExpr(lhs, rhs) => binary("<=", lhs, rhs),
+            Expression::GtExpr(lhs, rhs) => binary(">", lhs, rhs),
+            Expression::GteExpr(lhs, rhs) => binary(">=", lhs, rhs),
+            Expression::EqExpr(lhs, rhs) => binary("==", lhs, rhs),
+            Expression::NeExpr(lhs, rhs) => binary("!=", lhs, rhs),
+            Expression::AndExpr(lhs, rhs) => binary("and", lhs, rhs),
+            Expression::OrExpr(lhs, rhs) => binary("or", lhs, rhs),
+            Expression::BitAndExpr(lhs, rhs) => binary("&", lhs, rhs),
+            Expression::BitOrExpr(lhs, rhs) => binary("|", lhs, rhs),
+            Expression::BitXorExpr(lhs, rhs) => binary("^", lhs, rhs),
+            Expression::ShlExpr(lhs, rhs) => binary("<<", lhs, rhs),
+            Expression::ShrExpr(lhs, rhs) => binary(">>", lhs, rhs),
+            Expression::NegExpr(inner, _) => format!("(- {})", sexp(inner)),
+            Expression::NotExpr(inner, _) => format!("(not {})", sexp(inner)),
+            Expression::BitNotExpr(inner, _) => format!("(~ {})", sexp(inner)),
+            Expression::CastExpr { expr, ty, .. } => {
+                let ty = match ty {
+                    TypeExpr::Prim(token) | TypeExpr::Named(token) => token.content.as_str(),
+                    _ => "?",
+                };
+                format!("(as {} {ty})", sexp(expr))
+            }
+            Expression::CallExpr(callee, args, _) => {
+                let args = args.iter().map(|arg| format!(" {}", sexp(arg)));
+                format!("(call {}{})", sexp(callee), args.collect::<String>())
+            }
+            Expression::FieldExpr(base, field) => format!("(. {} {})", sexp(base), field.content),
+            Expression::IndexExpr(base, index, _) => binary("[]", base, index),
+            Expression::ParenExpr(inner, _) => format!("(paren {})", sexp(inner)),
+            Expression::Atom {
+                inner: Atom::Literal(token) | Atom::Identifier(token),
+            } => token.content.clone(),
+            Expression::IfExpr { .. } | Expression::BlockExpr(_) | Expression::LoopExpr { .. } => {
+                "{...}".to_string()
+            }
+        }
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        let cases = [
+            ("a + b * c", "(+ a (* b c))"),
+            ("a * b + c", "(+ (* a b) c)"),
+            ("a - b - c", "(- (- a b) c)"),
+            ("a / b * c", "(* (/ a b) c)"),
+            ("a / b % c", "(% (/ a b) c)"),
+            ("-a * b", "(* (- a) b)"),
+            ("- -a", "(- (- a))"),
+            ("not a == b", "(== (not a) b)"),
+            ("a == b and c or d", "(or (and (== a b) c) d)"),
+            ("a or b and c", "(or a (and b c))"),
+            ("a < b + c", "(< a (+ b c))"),
+            ("a <= b - c", "(<= a (- b c))"),
+            ("a + b > c * d", "(> (+ a b) (* c d))"),
+            ("a as u8 + b", "(+ (as a u8) b)"),
+            ("-a as s64", "(as (- a) s64)"),
+            ("a as u8 as u32", "(as (as a u8) u32)"),
+            ("a | b ^ c & d", "(| a (^ b (& c d)))"),
+            ("a << b + c", "(<< a (+ b c))"),
+            ("a & b == c", "(== (& a b) c)"),
+            ("~a & b", "(& (~ a) b)"),
+            ("!a != b", "(!= (not a) b)"),
+            ("a * (b + c)", "(* a (paren (+ b c)))"),
+            ("f(a, b + c).d[e]", "([] (. (call f a (+ b c)) d) e)"),
+            ("-f(a)[0]", "(- ([] (call f a) 0))"),
+        ];
+        for (src, expected) in cases {
+            let (tree, diagnostics) = parse_expr(src);
+            assert_eq!(diagnostics, [], "`{src}`");
+            assert_eq!(tree, expected, "`{src}`");
+        }
+    }
+
+    #[test]
+    fn comparisons_and_assignments_do_not_chain() {
+        let (tree, diagnostics) = parse_expr("a < b < c");
+        assert_eq!(tree, "(< (< a b) c)");
+        assert_eq!(
+            diagnostics,
+            [(
+                "comparison operators cannot be chained".to_string(),
+                "<".to_string()
+            )]
+        );
+
+        let (_, diagnostics) = parse("f :: proc () { a = b = c; }");
+        assert_eq!(
+            diagnostics,
+            [("expected `;`, found `=`".to_string(), "=".to_string())]
+        );
+    }
+
+    #[test]
+    fn if_else_chains_nest_in_the_else_branch() {
+        let (ast, diagnostics) = parse(
+            "f :: proc (a :: bool, b :: bool) -> s32 {
+                if a { work(); }
+                x := if a { 1 } else if b { 2 } else { 3 };
+                x
+            }",
+        );
+        assert_eq!(diagnostics, []);
+
+        let DeclarationDef::Function { def } = &ast.program.modules[0].declarations[0].def else {
+            panic!("`f` is a proc");
+        };
+        let [
+            Statement::Expr(Expression::IfExpr {
+                else_branch: None, ..
+            }),
+            Statement::Binding { value, .. },
+        ] = def.body.as_slice()
+        else {
+            panic!("`f` holds an `if` statement and a binding");
+        };
+        let Expression::IfExpr {
+            else_branch: Some(else_branch),
+            ..
+        } = value
+        else {
+            panic!("`x` is bound to an `if` with an `else`");
+        };
+        let Expression::IfExpr {
+            else_branch: Some(last),
+            ..
+        } = &**else_branch
+        else {
+            panic!("`else if` nests an `if` in the else branch");
+        };
+        assert!(matches!(&**last, Expression::BlockExpr(block) if block.expr.is_some()));
+    }
+
+    #[test]
+    fn malformed_ifs_are_reported() {
+        let (_, diagnostics) = parse(
+            "f :: proc () { if { work(); } }
+            g :: proc (a :: bool) { if a { work(); } else work(); }",
+        );
+        let expected = [
+            ("missing condition in `if` expression", "if"),
+            ("expected `{` or `if`, found identifier `work`", "work"),
+        ];
+        let expected = expected
+            .iter()
+            .map(|&(message, snippet)| (message.to_string(), snippet.to_string()))
+            .collect::<Vec<(String, String)>>();
+        assert_eq!(diagnostics, expected);
+    }
+
+    #[test]
+    fn labels_do_not_collide_with_names() {
+        let (ast, diagnostics) = parse(
+            "f :: proc () -> s32 {
+                outer := 1;
+                v := 'outer :: loop {
+                    for i in 0..3 { break 'outer outer; }
+                    break outer;
+                };
+                v
+            }",
+        );
+        assert_eq!(diagnostics, []);
+
+        let DeclarationDef::Function { def } = &ast.program.modules[0].declarations[0].def else {
+            panic!("`f` is a proc");
+        };
+        let [_, Statement::Binding { value, .. }] = def.body.as_slice() else {
+            panic!("`f` has two bindings");
+        };
+        let Expression::LoopExpr { label, body, .. } = value else {
+            panic!("`v` is bound to a loop");
+        };
+        assert_eq!(
+            label.as_ref().map(|label| label.content.as_str()),
+            Some("'outer")
+        );
+
+        let [
+            Statement::For { body: for_body, .. },
+            Statement::Break { label, value, .. },
+        ] = body.body.as_slice()
+        else {
+            panic!("The loop holds a `for` and a `break`");
+        };
+        assert!(label.is_none());
+        assert!(matches!(
+            value,
+            Some(Expression::Atom { inner: Atom::Identifier(name) }) if name.content == "outer"
+        ));
+
+        let [Statement::Break { label, value, .. }] = for_body.body.as_slice() else {
+            panic!("The `for` holds a `break`");
+        };
+        assert_eq!(
+            label.as_ref().map(|label| label.content.as_str()),
+            Some("'outer")
+        );
+        assert!(value.is_some());
+    }
+
+    #[test]
+    fn bool_literals_and_logical_operators() {
+        let (tree, diagnostics) = parse_expr("true and not false or x");
+        assert_eq!(diagnostics, []);
+        assert_eq!(tree, "(or (and true (not false)) x)");
+
+        let (ast, _) = parse("f :: proc () { x := false; }");
+        let DeclarationDef::Function { def } = &ast.program.modules[0].declarations[0].def else {
+            panic!("`f` is a proc");
+        };
+        assert!(matches!(
+            def.body.as_slice(),
+            [Statement::Binding {
+                value: Expression::Atom {
+                    inner: Atom::Literal(Token {
+                        kind: Some(TokenKind::BoolLiteral),
+                        ..
+                    })
+                },
+                ..
+            }]
+        ));
+    }
 }
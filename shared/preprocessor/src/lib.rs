@@ -1,31 +1,25 @@
-use std::collections::BTreeSet;
+use std::ops::Range;
 
+use diagnostics::{Diagnostic, codes};
 use module_manager::{Module, ModuleManager};
 use span::Span;
 
 pub struct Preprocessor<'preprocessor> {
     pub module_manager: &'preprocessor mut ModuleManager,
-    cursor: usize,
-    span: Span,
 }
 
 /// CTOR
 impl<'preprocessor> Preprocessor<'preprocessor> {
     pub fn new(module_manager: &'preprocessor mut ModuleManager) -> Self {
-        let span = Span::new();
-        Self {
-            module_manager,
-            cursor: 0,
-            span,
-        }
+        Self { module_manager }
     }
 }
 
 impl<'preprocessor> Preprocessor<'preprocessor> {
-    pub fn detect_nonvalid_chars(&self) {
+    pub fn detect_nonvalid_chars(&self) -> Vec<Diagnostic> {
         let modules = self.module_manager.get_ref();
 
-        let mut detect_nonvalid_chars = |module: &Module| {
+        let detect_nonvalid_chars = |module: &Module| -> Vec<Diagnostic> {
             let alpha_lower = vec![
                 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p',
                 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
@@ -38,7 +32,10 @@ impl<'preprocessor> Preprocessor<'preprocessor> {
 
             let numeric = vec!['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'];
 
-            let whitespace = vec![' ', '\n', '\t'];
+            let whitespace = vec![' ', '\n', '\t', '\r'];
+
+            // Part of identifiers and digit separator in number literals
+            let separator = vec!['_'];
 
             let punc = vec![';', '(', ')', '[', ']', '{', '}', ','];
 
@@ -46,85 +43,77 @@ impl<'preprocessor> Preprocessor<'preprocessor> {
                 '+', '-', '*', '/', '%', '<', '>', '!', '?', '&', '|', '^', '~', '=', ':', '.',
             ];
 
-            let white_list = vec![alpha_lower, alpha_upper, numeric, punc, whitespace, ops]
-                .into_iter()
-                .flat_map(|v| v)
-                .collect::<Vec<char>>();
-
-            let mut error_span = Span::new();
-            let invalid_char_found = modules.iter().any(|m| {
-                let module_chrs = m.src.chars();
-
-                module_chrs.enumerate().any(|(_, c)| {
-                    error_span.incre_from_char(c);
-                    !white_list.contains(&c)
+            let white_list = vec![
+                alpha_lower,
+                alpha_upper,
+                numeric,
+                separator,
+                punc,
+                whitespace,
+                ops,
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<char>>();
+
+            // String and char literals may hold any character
+            let literals = text_literal_ranges(&module.src);
+
+            module
+                .src
+                .char_indices()
+                .filter(|(offset, _)| !literals.iter().any(|literal| literal.contains(offset)))
+                .filter(|(_, c)| !white_list.contains(c))
+                .map(|(offset, c)| {
+                    let span = Span::new(module.id, offset, offset + c.len_utf8());
+                    Diagnostic::error(format!("invalid character `{}`", c.escape_default()))
+                        .with_code(codes::INVALID_CHARACTER)
+                        .with_primary(span, "not allowed in gemstone source")
+                        .with_suggestion(span, "remove this character", "")
                 })
-            });
-
-            if invalid_char_found {
-                panic!(
-                    "Found invalid character in module: {}\t{}:{}",
-                    module.path.display(),
-                    error_span.line_num,
-                    error_span.col_num
-                );
-            }
+                .collect()
         };
 
         // Non-mutable preprocessing
-        for module in modules {
-            detect_nonvalid_chars(module);
-        }
+        modules.iter().flat_map(detect_nonvalid_chars).collect()
     }
 
     pub fn strip_comments(&mut self) {
-        let mut modules = self.module_manager.get_mut_ref();
+        let modules = self.module_manager.get_mut_ref();
 
-        let mut strip_comments_for = |module: &mut Module| {
-            let src_len = module.src.len();
+        // Comments are blanked out rather than removed so that byte offsets,
+        // and therefore every `Span`, still line up with the module on disk.
+        let strip_comments_for = |module: &mut Module| {
+            let mut stripped = String::with_capacity(module.src.len());
+            let mut in_comment = false;
 
-            // While not at EOF
-            while self.cursor < src_len {
-                let Some(current_chr) = module.src.chars().nth(self.cursor) else {
-                    return;
-                };
+            // `//` inside a string or char literal does not start a comment
+            let literals = text_literal_ranges(&module.src);
 
-                // If we are at a blank newline
+            let mut chars = module.src.char_indices().peekable();
+            while let Some((offset, current_chr)) = chars.next() {
+                // Comments run until the end of the line
                 if current_chr == '\n' {
-                    self.cursor += 1;
-                    self.span.incre_line_num();
-                    self.span.reset_col_num();
+                    in_comment = false;
+                    stripped.push(current_chr);
                     continue;
                 }
 
-                // If we are at a tab
-                if current_chr == '\t' {
-                    module.src.remove(self.cursor);
-                    self.cursor += 4;
-                    self.span.incre_col_num_by(4);
-
-                    continue;
+                // Start of a single line comment
+                if current_chr == '/'
+                    && chars.peek().map(|&(_, c)| c) == Some('/')
+                    && !literals.iter().any(|literal| literal.contains(&offset))
+                {
+                    in_comment = true;
                 }
 
-                // Remove single newline comment
-                if current_chr == '/' {
-                    let next_chr = module.src.chars().nth(self.cursor + 1);
-                    if next_chr == Some('/') {
-                        module.src.remove(self.cursor);
-                        module.src.remove(self.cursor);
-
-                        let mut next_chr = module.src.chars().nth(self.cursor);
-                        while next_chr != Some('\n') {
-                            module.src.remove(self.cursor);
-                            next_chr = module.src.chars().nth(self.cursor);
-                        }
-
-                        // At a newline here!
-                        module.src.remove(self.cursor);
-                    }
+                match in_comment {
+                    true => stripped.extend(std::iter::repeat_n(' ', current_chr.len_utf8())),
+                    false => stripped.push(current_chr),
                 }
-                self.cursor += 1;
             }
+
+            module.src = stripped;
         };
 
         // Mutable preprocessing
@@ -133,3 +122,83 @@ impl<'preprocessor> Preprocessor<'preprocessor> {
         }
     }
 }
+
+// Byte ranges of the string and char literals in `src`, from the opening quote
+// (or the `r` of a raw string) to past the closing one. Unterminated strings run
+// to the end of `src` and unterminated chars to the end of the line; the scanner
+// reports them.
+fn text_literal_ranges(src: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut prev_chr: Option<char> = None;
+
+    let mut chars = src.char_indices().peekable();
+    while let Some((start, current_chr)) = chars.next() {
+        match current_chr {
+            // A quote in a comment does not start a literal
+            '/' if chars.peek().map(|&(_, c)| c) == Some('/') => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            }
+            '"' => {
+                let mut end = src.len();
+                while let Some((offset, c)) = chars.next() {
+                    match c {
+                        // Escaped char, possibly a `"`
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => {
+                            end = offset + 1;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+                ranges.push(start..end);
+            }
+            '\'' => {
+                let mut end = src.len();
+                while let Some(&(offset, c)) = chars.peek() {
+                    match c {
+                        '\n' => {
+                            end = offset;
+                            break;
+                        }
+                        '\\' => {
+                            chars.next();
+                            chars.next_if(|&(_, c)| c != '\n');
+                            continue;
+                        }
+                        '\'' => {
+                            chars.next();
+                            end = offset + 1;
+                            break;
+                        }
+                        _ => chars.next(),
+                    };
+                }
+                ranges.push(start..end);
+            }
+
+            // `r"..."` or `r#"..."#`, but not the `r` ending an identifier
+            'r' if !prev_chr.is_some_and(|c| c.is_alphanumeric() || c == '_') => {
+                let rest = &src[start + 1..];
+                let hashes = rest.len() - rest.trim_start_matches('#').len();
+                if rest[hashes..].starts_with('"') {
+                    let body_start = start + 1 + hashes + 1;
+                    let closing = format!("\"{}", "#".repeat(hashes));
+                    let end = src[body_start..]
+                        .find(&closing)
+                        .map_or(src.len(), |idx| body_start + idx + closing.len());
+
+                    while chars.next_if(|&(offset, _)| offset < end).is_some() {}
+                    ranges.push(start..end);
+                }
+            }
+            _ => (),
+        }
+
+        prev_chr = Some(current_chr);
+    }
+
+    ranges
+}
@@ -1,10 +1,71 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+mod source_map;
+
+pub use source_map::{SourceFile, SourceMap, SpanLine, TAB_WIDTH, display_width};
+
+/// Identifies the module (source file) a `Span` belongs to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(pub usize);
+
+/// A half-open byte range `[start, end)` into the source of the module `file`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize) -> Self {
+        assert!(start <= end, "Span start `{start}` is past its end `{end}`");
+        Self { file, start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Create a span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        assert_eq!(
+            self.file, other.file,
+            "Cannot join spans of different modules"
+        );
+        Span::new(
+            self.file,
+            self.start.min(other.start),
+            self.end.max(other.end),
+        )
+    }
+
+    /// Line and column of the first byte of the span within `src`.
+    pub fn start_location(&self, src: &str) -> Location {
+        Location::from_offset(src, self.start)
+    }
+
+    /// Line and column one past the last byte of the span within `src`.
+    pub fn end_location(&self, src: &str) -> Location {
+        Location::from_offset(src, self.end)
+    }
+}
+
+/// A 1-based line/column position in a module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
     pub line_num: usize,
     pub col_num: usize,
 }
 
-impl Span {
+impl Default for Location {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Location {
     pub fn new() -> Self {
         Self {
             line_num: 1,
@@ -16,47 +77,66 @@ impl Span {
         Self { line_num, col_num }
     }
 
-    pub fn reset_col_num(&mut self) {
-        self.col_num = 0;
-    }
+    /// Derive the location of the byte `offset` by walking `src`. Prefer
+    /// `SourceMap::start_location` when the module is registered.
+    pub fn from_offset(src: &str, offset: usize) -> Self {
+        let prefix = &src[..offset.min(src.len())];
+        let line_start = prefix.rfind('\n').map_or(0, |idx| idx + 1);
 
-    pub fn incre_line_num(&mut self) {
-        self.line_num += 1;
-    }
+        let line_num = prefix.matches('\n').count() + 1;
+        let col_num = prefix[line_start..].chars().count() + 1;
 
-    pub fn incre_col_num(&mut self) {
-        self.col_num += 1;
+        Self { line_num, col_num }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn incre_col_num_by(&mut self, offset: usize) {
-        self.col_num += offset;
+    #[test]
+    fn spans_are_half_open_byte_ranges() {
+        let span = Span::new(FileId(1), 3, 7);
+        assert_eq!(span.file, FileId(1));
+        assert_eq!(span.len(), 4);
+        assert!(!span.is_empty());
+        assert!(Span::new(FileId(1), 7, 7).is_empty());
+
+        // The default span is empty and points at the start of the first module
+        assert_eq!(Span::default(), Span::new(FileId(0), 0, 0));
     }
 
-    pub fn incre_line_num_by(&mut self, offset: usize) {
-        self.line_num += offset;
+    #[test]
+    fn joined_spans_cover_both() {
+        let a = Span::new(FileId(0), 2, 5);
+        let b = Span::new(FileId(0), 8, 10);
+        assert_eq!(a.to(b), Span::new(FileId(0), 2, 10));
+        assert_eq!(b.to(a), Span::new(FileId(0), 2, 10));
+
+        // Overlapping and nested spans
+        assert_eq!(a.to(Span::new(FileId(0), 4, 6)), Span::new(FileId(0), 2, 6));
+        assert_eq!(a.to(Span::new(FileId(0), 3, 4)), a);
     }
 
-    pub fn decre_line_num(&mut self) {
-        self.line_num -= 1;
+    #[test]
+    #[should_panic(expected = "Cannot join spans of different modules")]
+    fn spans_of_different_modules_do_not_join() {
+        let _ = Span::new(FileId(0), 0, 1).to(Span::new(FileId(1), 0, 1));
     }
 
-    pub fn decre_col_num(&mut self) {
-        self.col_num -= 1;
+    #[test]
+    #[should_panic(expected = "is past its end")]
+    fn spans_do_not_end_before_they_start() {
+        let _ = Span::new(FileId(0), 2, 1);
     }
 
-    pub fn incre_from_char(&mut self, chr: char) {
-        match chr {
-            '\n' => {
-                self.incre_line_num();
-                self.reset_col_num();
-            }
-            '\t' => {
-                self.incre_col_num_by(4);
-            }
-            _ => {
-                self.incre_col_num();
-            }
-        }
+    #[test]
+    fn span_locations_are_one_based() {
+        let src = "a := 1;\nbb := 22;\n";
+        let span = Span::new(FileId(0), 14, 16);
+        assert_eq!(&src[span.start..span.end], "22");
+        assert_eq!(span.start_location(src), Location::new_with(2, 7));
+        assert_eq!(span.end_location(src), Location::new_with(2, 9));
+        assert_eq!(Span::default().start_location(src), Location::new());
     }
 }
-
@@ -0,0 +1,345 @@
+use std::{ops::Range, path::PathBuf};
+
+use crate::{FileId, Location, Span};
+
+/// Number of columns a tab advances to when rendering source lines.
+pub const TAB_WIDTH: usize = 4;
+
+/// The text of a single module together with the byte offset each of its lines starts at.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub id: FileId,
+    pub path: PathBuf,
+    pub src: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(id: FileId, path: PathBuf, src: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect::<Vec<usize>>();
+
+        Self {
+            id,
+            path,
+            src,
+            line_starts,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// 0-based index of the line containing `offset`.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line_idx) => line_idx,
+            Err(next_line_idx) => next_line_idx - 1,
+        }
+    }
+
+    /// Byte range of line `line_num` (1-based), excluding its line terminator.
+    pub fn line_range(&self, line_num: usize) -> Range<usize> {
+        assert!(
+            line_num >= 1 && line_num <= self.line_count(),
+            "Line `{line_num}` is out of range for `{}`",
+            self.path.display()
+        );
+
+        let start = self.line_starts[line_num - 1];
+        let end = self
+            .line_starts
+            .get(line_num)
+            .map_or(self.src.len(), |next_start| next_start - 1);
+
+        let line = &self.src[start..end];
+        start..start + line.strip_suffix('\r').unwrap_or(line).len()
+    }
+
+    /// Text of line `line_num` (1-based), excluding its line terminator.
+    pub fn line_text(&self, line_num: usize) -> &str {
+        &self.src[self.line_range(line_num)]
+    }
+
+    /// 1-based line and (character based) column of `offset`.
+    pub fn location(&self, offset: usize) -> Location {
+        let offset = self.clamp_to_char_boundary(offset);
+        let line_idx = self.line_index(offset);
+        let line_start = self.line_starts[line_idx];
+
+        Location::new_with(
+            line_idx + 1,
+            self.src[line_start..offset].chars().count() + 1,
+        )
+    }
+
+    /// 1-based column of `offset` as displayed in a terminal, with tabs expanded.
+    pub fn display_col(&self, offset: usize) -> usize {
+        let offset = self.clamp_to_char_boundary(offset);
+        let line_start = self.line_starts[self.line_index(offset)];
+
+        display_width(&self.src[line_start..offset]) + 1
+    }
+
+    /// Source text covered by `span`.
+    pub fn snippet(&self, span: Span) -> &str {
+        assert_eq!(span.file, self.id, "Span belongs to a different module");
+        let start = self.clamp_to_char_boundary(span.start);
+        let end = self.clamp_to_char_boundary(span.end);
+
+        &self.src[start..end]
+    }
+
+    /// Every line touched by `span`, with the part of the line the span covers.
+    pub fn span_lines(&self, span: Span) -> Vec<SpanLine<'_>> {
+        assert_eq!(span.file, self.id, "Span belongs to a different module");
+        let start = self.clamp_to_char_boundary(span.start);
+        let end = self.clamp_to_char_boundary(span.end);
+
+        let first_line = self.line_index(start) + 1;
+        let last_line = match end > start {
+            // An exclusive end sitting right at a line start does not touch that line
+            true => self.line_index(end - 1) + 1,
+            false => first_line,
+        };
+
+        (first_line..=last_line)
+            .map(|line_num| {
+                let line_range = self.line_range(line_num);
+                let highlight_start = start.max(line_range.start) - line_range.start;
+                let highlight_end =
+                    end.min(line_range.end).max(start.max(line_range.start)) - line_range.start;
+
+                SpanLine {
+                    line_num,
+                    text: &self.src[line_range],
+                    highlight: highlight_start..highlight_end,
+                }
+            })
+            .collect()
+    }
+
+    fn clamp_to_char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.src.len());
+        while !self.src.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+}
+
+/// One line of a (possibly multi-line) span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanLine<'src> {
+    pub line_num: usize,
+    pub text: &'src str,
+
+    // Byte range within `text` covered by the span
+    pub highlight: Range<usize>,
+}
+
+/// Owns the text of every module and answers offset to line/column queries.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self { files: Vec::new() }
+    }
+
+    /// Register a module's text, building its line index once.
+    pub fn add_file(&mut self, path: PathBuf, src: String) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(SourceFile::new(id, path, src));
+        id
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0]
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Location of the first byte of `span`.
+    pub fn start_location(&self, span: Span) -> Location {
+        self.get(span.file).location(span.start)
+    }
+
+    /// Location one past the last byte of `span`.
+    pub fn end_location(&self, span: Span) -> Location {
+        self.get(span.file).location(span.end)
+    }
+
+    pub fn snippet(&self, span: Span) -> &str {
+        self.get(span.file).snippet(span)
+    }
+
+    pub fn span_lines(&self, span: Span) -> Vec<SpanLine<'_>> {
+        self.get(span.file).span_lines(span)
+    }
+}
+
+/// Number of terminal columns `text` occupies, with tabs advancing to the next tab stop.
+pub fn display_width(text: &str) -> usize {
+    text.chars().fold(0, |width, chr| match chr {
+        '\t' => width + TAB_WIDTH - width % TAB_WIDTH,
+        _ => width + 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(src: &str) -> SourceFile {
+        SourceFile::new(FileId(0), PathBuf::from("test.gem"), src.to_string())
+    }
+
+    fn span(start: usize, end: usize) -> Span {
+        Span::new(FileId(0), start, end)
+    }
+
+    #[test]
+    fn location_counts_lines_and_chars() {
+        let file = source("ab\ncd\n");
+        assert_eq!(file.location(0), Location::new_with(1, 1));
+        assert_eq!(file.location(1), Location::new_with(1, 2));
+        assert_eq!(file.location(2), Location::new_with(1, 3));
+        assert_eq!(file.location(3), Location::new_with(2, 1));
+        assert_eq!(file.location(4), Location::new_with(2, 2));
+    }
+
+    #[test]
+    fn location_at_end_of_file() {
+        let file = source("ab\ncd");
+        assert_eq!(file.location(5), Location::new_with(2, 3));
+        // Offsets past the end are clamped to it
+        assert_eq!(file.location(42), Location::new_with(2, 3));
+
+        let file = source("ab\n");
+        assert_eq!(file.location(3), Location::new_with(2, 1));
+        assert_eq!(file.line_count(), 2);
+        assert_eq!(file.line_text(2), "");
+    }
+
+    #[test]
+    fn location_counts_multi_byte_chars_once() {
+        // `é` is 2 bytes and `€` is 3 bytes
+        let file = source("é€x\ny");
+        assert_eq!(file.location(2), Location::new_with(1, 2));
+        assert_eq!(file.location(5), Location::new_with(1, 3));
+        assert_eq!(file.location(6), Location::new_with(1, 4));
+        assert_eq!(file.location(7), Location::new_with(2, 1));
+
+        // Offsets inside a char are moved back to its first byte
+        assert_eq!(file.location(3), Location::new_with(1, 2));
+        assert_eq!(file.snippet(span(1, 4)), "é");
+    }
+
+    #[test]
+    fn location_agrees_with_from_offset() {
+        let src = "a\tb\r\nçd\n\ne";
+        let file = source(src);
+        for offset in (0..=src.len()).filter(|&offset| src.is_char_boundary(offset)) {
+            assert_eq!(
+                file.location(offset),
+                Location::from_offset(src, offset),
+                "offset {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn display_col_expands_tabs() {
+        let file = source("\tx\nab\tc\n\t\ty");
+        assert_eq!(file.location(1).col_num, 2);
+        assert_eq!(file.display_col(1), TAB_WIDTH + 1);
+
+        // A tab after two columns only advances to the next tab stop
+        assert_eq!(file.display_col(6), TAB_WIDTH + 1);
+        assert_eq!(file.display_col(10), 2 * TAB_WIDTH + 1);
+
+        assert_eq!(display_width("abcd\t"), 2 * TAB_WIDTH);
+        assert_eq!(display_width("é"), 1);
+    }
+
+    #[test]
+    fn crlf_is_not_part_of_the_line() {
+        let file = source("ab\r\ncd\r\n");
+        assert_eq!(file.line_count(), 3);
+        assert_eq!(file.line_range(1), 0..2);
+        assert_eq!(file.line_text(1), "ab");
+        assert_eq!(file.line_text(2), "cd");
+        assert_eq!(file.location(4), Location::new_with(2, 1));
+    }
+
+    #[test]
+    fn span_lines_single_line() {
+        let file = source("let x\nfoo bar\n");
+        assert_eq!(
+            file.span_lines(span(10, 13)),
+            vec![SpanLine {
+                line_num: 2,
+                text: "foo bar",
+                highlight: 4..7,
+            }]
+        );
+    }
+
+    #[test]
+    fn span_lines_multi_line() {
+        let file = source("ab\r\ncd\nef\n");
+        assert_eq!(
+            file.span_lines(span(1, 8)),
+            vec![
+                SpanLine {
+                    line_num: 1,
+                    text: "ab",
+                    highlight: 1..2,
+                },
+                SpanLine {
+                    line_num: 2,
+                    text: "cd",
+                    highlight: 0..2,
+                },
+                SpanLine {
+                    line_num: 3,
+                    text: "ef",
+                    highlight: 0..1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn span_lines_end_at_line_start_does_not_touch_next_line() {
+        let file = source("ab\ncd");
+        let lines = file.span_lines(span(0, 3));
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].highlight, 0..2);
+
+        // Empty spans still mark the line they sit on
+        let lines = file.span_lines(span(5, 5));
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].line_num, 2);
+        assert_eq!(lines[0].highlight, 2..2);
+    }
+
+    #[test]
+    fn source_map_looks_up_by_file() {
+        let mut map = SourceMap::new();
+        map.add_file(PathBuf::from("a.gem"), "aaa".to_string());
+        let b = map.add_file(PathBuf::from("b.gem"), "b\nbb".to_string());
+
+        let span = Span::new(b, 2, 4);
+        assert_eq!(map.snippet(span), "bb");
+        assert_eq!(map.start_location(span), Location::new_with(2, 1));
+        assert_eq!(map.end_location(span), Location::new_with(2, 3));
+    }
+}
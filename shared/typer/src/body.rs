@@ -0,0 +1,1788 @@
+use std::collections::HashMap;
+
+use diagnostics::{Diagnostic, codes};
+use lexical_analyzer::{Token, TokenKind};
+use parser::{
+    AssignOp, Atom, Block, Expression, FunctionDef, Mutability, Param, Statement, TypeExpr,
+    Visibility,
+};
+use span::{FileId, Span};
+
+use crate::items::ItemTypes;
+use crate::symbols::{DeclId, DeclKind, SymbolTable};
+use crate::{
+    DEFAULT_FLOAT, DEFAULT_INT, InferKind, InferVar, ItemKind, Primitive, Resolution, Type,
+    eval_ty, eval_ty_from_literal, num_suffix,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoopKind {
+    Loop,
+    While,
+    For,
+}
+
+/// Loop enclosing the statement being checked.
+#[derive(Debug)]
+struct LoopFrame {
+    label: Option<String>,
+    kind: LoopKind,
+
+    // Whether a `break` leaves this loop, and the type of the value it gives
+    has_break: bool,
+    break_ty: Option<Type>,
+}
+
+/// Inference variable created for an unsuffixed literal.
+#[derive(Debug)]
+struct InferSlot {
+    kind: InferKind,
+
+    // Type the variable was unified with, possibly another variable
+    ty: Option<Type>,
+
+    // Set if nothing constrained the literal and it got its default type
+    defaulted: bool,
+}
+
+/// Integer literal, checked against its type once every literal is inferred.
+#[derive(Debug)]
+struct IntLiteral {
+    // As written, for reporting
+    content: String,
+
+    // `None` if too large for any type
+    value: Option<u128>,
+    ty: Type,
+    negated: bool,
+
+    // Includes the `-` of negated literals
+    span: Span,
+}
+
+/// Float literal, checked against its type once every literal is inferred.
+#[derive(Debug)]
+struct FloatLiteral {
+    // As written, for reporting
+    content: String,
+    value: Option<f64>,
+    ty: Type,
+    span: Span,
+}
+
+/// Type checks a single proc body: every expression is given a type where it
+/// can be determined, and operators, calls, fields, assignments and returns are
+/// checked against it.
+pub(crate) struct BodyChecker<'a> {
+    module: FileId,
+    resolution: &'a Resolution,
+    symbols: &'a SymbolTable,
+    items: &'a ItemTypes,
+
+    // Declared return type (`()` if none) and where it is written
+    ret_ty: Option<Type>,
+    ret_span: Option<Span>,
+
+    // Types of the locals whose type is known
+    local_tys: HashMap<DeclId, Type>,
+
+    // Indexed by `InferVar::id`
+    infer_slots: Vec<InferSlot>,
+    int_literals: Vec<IntLiteral>,
+    float_literals: Vec<FloatLiteral>,
+
+    // Negated operands whose type was still being inferred, with their span.
+    // Checked to be signed once every literal is inferred.
+    negations: Vec<(Type, Span)>,
+
+    // Innermost loop last
+    loops: Vec<LoopFrame>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// CTOR
+impl<'a> BodyChecker<'a> {
+    pub(crate) fn new(
+        module: FileId,
+        resolution: &'a Resolution,
+        symbols: &'a SymbolTable,
+        items: &'a ItemTypes,
+    ) -> Self {
+        Self {
+            module,
+            resolution,
+            symbols,
+            items,
+            ret_ty: Some(Type::Unit),
+            ret_span: None,
+            local_tys: HashMap::new(),
+            infer_slots: Vec::new(),
+            int_literals: Vec::new(),
+            float_literals: Vec::new(),
+            negations: Vec::new(),
+            loops: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+}
+
+impl BodyChecker<'_> {
+    /// Check `def`, whose names were resolved into the symbol table before.
+    pub(crate) fn check_function(
+        mut self,
+        params: &[Param],
+        output: Option<&TypeExpr>,
+        def: &FunctionDef,
+    ) -> Vec<Diagnostic> {
+        // Broken parameter and return types are reported with the signature
+        for param in params {
+            let ty = eval_ty(&param.ty, self.module, self.resolution).ok();
+            self.set_ty(&param.name, ty);
+        }
+        if let Some(output) = output {
+            self.ret_ty = eval_ty(output, self.module, self.resolution).ok();
+            self.ret_span = output.span();
+        }
+
+        for statement in &def.body {
+            self.check_statement(statement);
+        }
+
+        match &def.expr {
+            Some(expr) => {
+                let expr_ty = self.expr_ty(expr);
+                if let Some(ret_ty) = self.ret_ty.clone() {
+                    self.expect_ty(&ret_ty, expr_ty.as_ref(), expr);
+                }
+            }
+
+            // Falling off the end of the body returns `()`
+            None if !matches!(def.body.last(), Some(Statement::Return { .. })) => {
+                let ret_ty = self.ret_ty.as_ref().map(|ty| self.resolve_ty(ty));
+                if let (Some(ret_ty), Some(ret_span)) = (ret_ty, self.ret_span)
+                    && ret_ty != Type::Unit
+                {
+                    self.diagnostics.push(
+                        Diagnostic::error("mismatched types")
+                            .with_code(codes::MISMATCHED_TYPES)
+                            .with_primary(ret_span, format!("expected `{ret_ty}`, found `()`"))
+                            .with_note("the body ends without a tail expression")
+                            .with_help("end the body with the value to return, without a `;`"),
+                    );
+                }
+            }
+            None => (),
+        }
+
+        self.default_literals();
+        self.check_negations();
+        self.check_int_literals();
+        self.check_float_literals();
+        self.diagnostics
+    }
+
+    // Literals whose type was not inferred from their uses get a default type
+    fn default_literals(&mut self) {
+        for slot in &mut self.infer_slots {
+            if slot.ty.is_some() {
+                continue;
+            }
+
+            let default = match slot.kind {
+                InferKind::Int => DEFAULT_INT,
+                InferKind::Float => DEFAULT_FLOAT,
+            };
+            slot.ty = Some(Type::Prim(default));
+            slot.defaulted = true;
+        }
+    }
+
+    // Whether a literal's type is the default rather than inferred
+    fn is_defaulted(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Infer(var) => match &self.infer_slots[var.id].ty {
+                Some(Type::Infer(other)) => self.is_defaulted(&Type::Infer(*other)),
+                _ => self.infer_slots[var.id].defaulted,
+            },
+            _ => false,
+        }
+    }
+
+    // Operands negated before their type was known must turn out signed
+    fn check_negations(&mut self) {
+        for (ty, span) in std::mem::take(&mut self.negations) {
+            let ty = self.resolve_ty(&ty);
+            if !is_signed(&ty) {
+                self.diagnostics.push(negation_error(&ty, span));
+            }
+        }
+    }
+
+    // Integer literals must fit the type they were given or inferred
+    fn check_int_literals(&mut self) {
+        for literal in std::mem::take(&mut self.int_literals) {
+            let Type::Prim(prim) = self.resolve_ty(&literal.ty) else {
+                continue;
+            };
+            let Some((min, max)) = prim.int_range() else {
+                continue;
+            };
+
+            let value = literal
+                .value
+                .and_then(|value| i128::try_from(value).ok())
+                .map(|value| if literal.negated { -value } else { value });
+            if value.is_some_and(|value| (min..=max).contains(&value)) {
+                continue;
+            }
+
+            let written = match literal.negated {
+                true => format!("-{}", literal.content),
+                false => literal.content,
+            };
+            let mut diagnostic = Diagnostic::error(format!("literal out of range for `{prim}`"))
+                .with_code(codes::LITERAL_OUT_OF_RANGE)
+                .with_primary(
+                    literal.span,
+                    format!("`{written}` does not fit in `{prim}`"),
+                )
+                .with_note(format!("the range of `{prim}` is `{min}` to `{max}`"));
+
+            // Smallest type of the same signedness the value fits in
+            let fits = value.and_then(|value| {
+                let candidates = match value < 0 {
+                    true => [
+                        Primitive::S8,
+                        Primitive::S16,
+                        Primitive::S32,
+                        Primitive::S64,
+                    ],
+                    false if min == 0 => [
+                        Primitive::U8,
+                        Primitive::U16,
+                        Primitive::U32,
+                        Primitive::U64,
+                    ],
+                    false => [
+                        Primitive::S8,
+                        Primitive::S16,
+                        Primitive::S32,
+                        Primitive::S64,
+                    ],
+                };
+                candidates.into_iter().find(|ty| {
+                    ty.int_range()
+                        .is_some_and(|(min, max)| (min..=max).contains(&value))
+                })
+            });
+            if self.is_defaulted(&literal.ty) {
+                diagnostic = diagnostic.with_note(format!(
+                    "nothing constrains the literal's type, so it defaults to `{prim}`"
+                ));
+            }
+            diagnostic = match fits {
+                Some(fits) if min == 0 && literal.negated => diagnostic.with_help(format!(
+                    "unsigned types cannot hold negative values; use a signed type such as `{fits}`"
+                )),
+                Some(fits) => diagnostic.with_help(format!("use a wider type such as `{fits}`")),
+                None => diagnostic,
+            };
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    // Float literals must not overflow to infinity in their type
+    fn check_float_literals(&mut self) {
+        for literal in std::mem::take(&mut self.float_literals) {
+            let (max, largest) = match self.resolve_ty(&literal.ty) {
+                Type::Prim(Primitive::F32) => (f32::MAX as f64, format!("{:e}", f32::MAX)),
+                Type::Prim(Primitive::F64) => (f64::MAX, format!("{:e}", f64::MAX)),
+                _ => continue,
+            };
+            let Some(value) = literal.value.filter(|value| value.abs() > max) else {
+                continue;
+            };
+
+            let ty = self.resolve_ty(&literal.ty);
+            let mut diagnostic = Diagnostic::error(format!("literal out of range for `{ty}`"))
+                .with_code(codes::LITERAL_OUT_OF_RANGE)
+                .with_primary(
+                    literal.span,
+                    format!("`{}` does not fit in `{ty}`", literal.content),
+                )
+                .with_note(format!("the largest `{ty}` is `{largest}`"));
+            if value.is_finite() {
+                diagnostic = diagnostic.with_help("use `f64`");
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    // Follow the types inference variables were unified with
+    fn resolve_ty(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Infer(var) => match &self.infer_slots[var.id].ty {
+                Some(ty) => self.resolve_ty(ty),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    // Resolve the types of a failed unification for reporting. Literals among
+    // them get their default type, so that they are not reported again.
+    fn mismatched(&mut self, a: &Type, b: &Type) -> (Type, Type) {
+        let (a, b) = (self.resolve_ty(a), self.resolve_ty(b));
+        self.settle(&a);
+        self.settle(&b);
+        (a, b)
+    }
+
+    // Give a literal its default type without reporting it
+    fn settle(&mut self, ty: &Type) {
+        if let Type::Infer(var) = self.resolve_ty(ty) {
+            let default = match var.kind {
+                InferKind::Int => DEFAULT_INT,
+                InferKind::Float => DEFAULT_FLOAT,
+            };
+            self.infer_slots[var.id].ty = Some(Type::Prim(default));
+        }
+    }
+
+    // Make `a` and `b` the same type by binding inference variables. Fails for
+    // different concrete types and for literals that cannot have the other type.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.resolve_ty(a), self.resolve_ty(b));
+        if a == b {
+            return true;
+        }
+
+        match (&a, &b) {
+            (Type::Infer(var), other) | (other, Type::Infer(var)) => {
+                if !var.kind.accepts(other) {
+                    return false;
+                }
+
+                self.infer_slots[var.id].ty = Some(other.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Binding {
+                name, ty, value, ..
+            } => {
+                let value_ty = self.expr_ty(value);
+                let ty = match ty {
+                    Some(ty) => match eval_ty(ty, self.module, self.resolution) {
+                        Ok(ty) => {
+                            self.expect_ty(&ty, value_ty.as_ref(), value);
+                            Some(ty)
+                        }
+                        Err(diagnostic) => {
+                            // The value's type is unknowable, so is the literal's
+                            if let Some(value_ty) = &value_ty {
+                                self.settle(value_ty);
+                            }
+                            self.diagnostics.push(*diagnostic);
+                            None
+                        }
+                    },
+                    None => value_ty,
+                };
+
+                self.set_ty(name, ty);
+            }
+            Statement::Assign { target, value } => {
+                if !self.check_mutable(target) {
+                    self.expr_ty(value);
+                    return;
+                }
+                let target_ty = self.expr_ty(target);
+                let value_ty = self.expr_ty(value);
+                if let Some(target_ty) = target_ty {
+                    self.expect_ty(&target_ty, value_ty.as_ref(), value);
+                }
+            }
+            Statement::CompoundAssign { target, op, value } => {
+                if !self.check_mutable(target) {
+                    self.expr_ty(value);
+                    return;
+                }
+                match op {
+                    AssignOp::Add => self.operands_ty("+=", target, value, is_numeric, "a number"),
+                    AssignOp::Sub => self.operands_ty("-=", target, value, is_numeric, "a number"),
+                    AssignOp::Mul => self.operands_ty("*=", target, value, is_numeric, "a number"),
+                    AssignOp::Div => self.operands_ty("/=", target, value, is_numeric, "a number"),
+                    AssignOp::Mod => self.operands_ty("%=", target, value, is_numeric, "a number"),
+                    AssignOp::BitAnd => {
+                        self.operands_ty("&=", target, value, is_integer, "an integer")
+                    }
+                    AssignOp::BitOr => {
+                        self.operands_ty("|=", target, value, is_integer, "an integer")
+                    }
+                    AssignOp::BitXor => {
+                        self.operands_ty("^=", target, value, is_integer, "an integer")
+                    }
+                    AssignOp::Shl => self.shift_ty("<<=", target, value),
+                    AssignOp::Shr => self.shift_ty(">>=", target, value),
+                };
+            }
+            Statement::Expr(expr) => {
+                self.expr_ty(expr);
+            }
+            Statement::Return { value, span } => {
+                let value_ty = match value {
+                    Some(value) => self.expr_ty(value),
+                    None => Some(Type::Unit),
+                };
+                let (Some(ret_ty), Some(value_ty)) = (self.ret_ty.clone(), value_ty) else {
+                    return;
+                };
+                if self.unify(&ret_ty, &value_ty) {
+                    return;
+                }
+                let (ret_ty, value_ty) = self.mismatched(&ret_ty, &value_ty);
+
+                let value_span = value.as_ref().map_or(*span, |value| value.span());
+                let mut diagnostic = Diagnostic::error("mismatched types")
+                    .with_code(codes::MISMATCHED_TYPES)
+                    .with_primary(
+                        value_span,
+                        format!("expected `{ret_ty}`, found `{value_ty}`"),
+                    );
+                if let Some(ret_span) = self.ret_span {
+                    diagnostic =
+                        diagnostic.with_secondary(ret_span, "expected because of this return type");
+                }
+                self.diagnostics.push(diagnostic);
+            }
+            Statement::While {
+                label, cond, body, ..
+            } => {
+                let cond_ty = self.expr_ty(cond);
+                self.expect_ty(&Type::Prim(Primitive::Bool), cond_ty.as_ref(), cond);
+                self.loop_body_ty(label.as_ref(), LoopKind::While, body);
+            }
+            Statement::For {
+                label,
+                var,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                let start_ty = self.expr_ty(start);
+                let end_ty = self.expr_ty(end);
+                if let Some(start_ty) = &start_ty {
+                    self.expect_ty(start_ty, end_ty.as_ref(), end);
+                }
+
+                // Ranges count through integers
+                let bound_ty = start_ty.or(end_ty).map(|ty| self.resolve_ty(&ty));
+                if let Some(bound_ty) = bound_ty.as_ref().filter(|ty| !is_integer(ty)) {
+                    self.settle(bound_ty);
+                    let bound_ty = self.resolve_ty(bound_ty);
+                    self.diagnostics.push(
+                        Diagnostic::error("mismatched types")
+                            .with_code(codes::MISMATCHED_TYPES)
+                            .with_primary(
+                                start.span().to(end.span()),
+                                format!("expected an integer, found `{bound_ty}`"),
+                            )
+                            .with_note("`for` loops count through a range of integers"),
+                    );
+                }
+
+                self.set_ty(var, bound_ty);
+                self.loop_body_ty(label.as_ref(), LoopKind::For, body);
+            }
+            Statement::Break { label, value, span } => {
+                let value_ty = value.as_ref().and_then(|value| self.expr_ty(value));
+                let Some(target) = self.loop_target("break", label.as_ref(), *span) else {
+                    return;
+                };
+
+                let frame = &self.loops[target];
+                if let (Some(value), LoopKind::While | LoopKind::For) = (value, frame.kind) {
+                    let keyword = match frame.kind {
+                        LoopKind::While => "while",
+                        _ => "for",
+                    };
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("`break` with value from a `{keyword}` loop"))
+                            .with_code(codes::BREAK_WITH_VALUE)
+                            .with_primary(value.span(), "can only break with a value inside `loop`")
+                            .with_help(format!(
+                                "remove the value or use `loop` instead of `{keyword}`"
+                            )),
+                    );
+                    return;
+                }
+
+                // `break;` leaves a `loop` without a value
+                let value_ty = match value {
+                    Some(_) => value_ty,
+                    None => Some(Type::Unit),
+                };
+                let previous_ty = self.loops[target].break_ty.clone();
+                match (previous_ty, value_ty) {
+                    (Some(previous_ty), Some(value_ty)) if !self.unify(&previous_ty, &value_ty) => {
+                        let (previous_ty, value_ty) = self.mismatched(&previous_ty, &value_ty);
+                        let span = value.as_ref().map_or(*span, |value| value.span());
+                        self.diagnostics.push(
+                            Diagnostic::error("mismatched types")
+                                .with_code(codes::MISMATCHED_TYPES)
+                                .with_primary(
+                                    span,
+                                    format!("expected `{previous_ty}`, found `{value_ty}`"),
+                                )
+                                .with_note(
+                                    "every `break` of a loop must give a value of the same type",
+                                ),
+                        );
+                    }
+                    (None, Some(value_ty)) => self.loops[target].break_ty = Some(value_ty),
+                    _ => (),
+                }
+                self.loops[target].has_break = true;
+            }
+            Statement::Continue { label, span } => {
+                self.loop_target("continue", label.as_ref(), *span);
+            }
+        }
+    }
+
+    // Check the body of a loop with `label` in scope for `break` and `continue`
+    fn loop_body_ty(&mut self, label: Option<&Token>, kind: LoopKind, body: &Block) -> LoopFrame {
+        self.loops.push(LoopFrame {
+            label: label.map(|label| label.content.clone()),
+            kind,
+            has_break: false,
+            break_ty: None,
+        });
+        self.block_ty(body);
+        self.loops.pop().expect("Loop frame was pushed")
+    }
+
+    // Index of the loop a `break` or `continue` jumps out of
+    fn loop_target(&mut self, keyword: &str, label: Option<&Token>, span: Span) -> Option<usize> {
+        let target = match label {
+            Some(label) => self
+                .loops
+                .iter()
+                .rposition(|frame| frame.label.as_deref() == Some(label.content.as_str())),
+            None => self.loops.len().checked_sub(1),
+        };
+        if target.is_some() {
+            return target;
+        }
+
+        let diagnostic = match label {
+            Some(label) => {
+                Diagnostic::error(format!("use of undeclared label `{}`", label.content))
+                    .with_code(codes::UNDECLARED_LABEL)
+                    .with_primary(label.span, "no enclosing loop has this label")
+                    .with_help(format!(
+                        "label a loop with `{} :: loop {{ ... }}`",
+                        label.content
+                    ))
+            }
+            None => Diagnostic::error(format!("`{keyword}` outside of a loop"))
+                .with_code(codes::BREAK_OUTSIDE_LOOP)
+                .with_primary(span, format!("cannot `{keyword}` outside of a loop")),
+        };
+        self.diagnostics.push(diagnostic);
+        None
+    }
+
+    // Record the type of the local declared by `name`
+    fn set_ty(&mut self, name: &Token, ty: Option<Type>) {
+        if let (Some(id), Some(ty)) = (self.symbols.resolve(name), ty) {
+            self.local_tys.insert(id, ty);
+        }
+    }
+
+    // Assigning to a variable, or to a field or element of it, requires `mut`.
+    // Returns `false` if the target is a proc, which is not a variable at all.
+    fn check_mutable(&mut self, target: &Expression) -> bool {
+        let Some(root) = place_root(target) else {
+            return true;
+        };
+        let Some(id) = self.symbols.resolve(root) else {
+            return true;
+        };
+        let local = self.symbols.get(id);
+
+        let diagnostic = match local.kind {
+            // Using a struct or enum as a value is reported by `expr_ty()`
+            DeclKind::Local(Mutability::Mutable)
+            | DeclKind::Item(ItemKind::Struct | ItemKind::Enum) => return true,
+            DeclKind::Item(ItemKind::Proc) => {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("cannot assign to proc `{}`", root.content))
+                        .with_code(codes::ASSIGN_TO_IMMUTABLE)
+                        .with_primary(target.span(), "cannot assign to a proc")
+                        .with_secondary(local.span, "proc declared here")
+                        .with_note("procs are not variables"),
+                );
+                return false;
+            }
+            DeclKind::Param => Diagnostic::error(format!(
+                "cannot assign to immutable parameter `{}`",
+                root.content
+            ))
+            .with_code(codes::ASSIGN_TO_IMMUTABLE)
+            .with_primary(target.span(), "cannot assign to parameter")
+            .with_secondary(local.span, "parameter declared here")
+            .with_help("copy the parameter into a `mut` local and assign to that"),
+            DeclKind::LoopVar => {
+                Diagnostic::error(format!("cannot assign to loop variable `{}`", root.content))
+                    .with_code(codes::ASSIGN_TO_IMMUTABLE)
+                    .with_primary(target.span(), "cannot assign to loop variable")
+                    .with_secondary(local.span, "loop variable declared here")
+            }
+            DeclKind::Local(Mutability::Immutable) => {
+                let name_start = Span::new(local.span.file, local.span.start, local.span.start);
+                Diagnostic::error(format!(
+                    "cannot assign twice to immutable variable `{}`",
+                    root.content
+                ))
+                .with_code(codes::ASSIGN_TO_IMMUTABLE)
+                .with_primary(target.span(), "cannot assign to immutable variable")
+                .with_secondary(local.span, "first bound here")
+                .with_suggestion(name_start, "make the binding mutable", "mut ")
+            }
+        };
+        self.diagnostics.push(diagnostic);
+        true
+    }
+
+    // Report `value` if its type is known and differs from `expected`
+    fn expect_ty(&mut self, expected: &Type, found: Option<&Type>, value: &Expression) {
+        let Some(found) = found else {
+            return;
+        };
+        if self.unify(expected, found) {
+            return;
+        }
+        let (expected, found) = self.mismatched(expected, found);
+
+        self.diagnostics.push(
+            Diagnostic::error("mismatched types")
+                .with_code(codes::MISMATCHED_TYPES)
+                .with_primary(
+                    value.span(),
+                    format!("expected `{expected}`, found `{found}`"),
+                ),
+        );
+    }
+
+    /// Type of `expr`, if it can be determined. Unsuffixed literals get an
+    /// inference variable, bound by unification with the types around them.
+    fn expr_ty(&mut self, expr: &Expression) -> Option<Type> {
+        match expr {
+            Expression::Atom { inner } => match inner {
+                Atom::Literal(token) => self.literal_ty(token),
+                Atom::Identifier(token) => self.identifier_ty(token),
+            },
+            Expression::ParenExpr(inner, _) => self.expr_ty(inner),
+            Expression::AddExpr(lhs, rhs) => {
+                self.operands_ty("+", lhs, rhs, is_numeric, "a number")
+            }
+            Expression::SubExpr(lhs, rhs) => {
+                self.operands_ty("-", lhs, rhs, is_numeric, "a number")
+            }
+            Expression::MulExpr(lhs, rhs) => {
+                self.operands_ty("*", lhs, rhs, is_numeric, "a number")
+            }
+            Expression::DivExpr(lhs, rhs) => {
+                self.operands_ty("/", lhs, rhs, is_numeric, "a number")
+            }
+            Expression::ModExpr(lhs, rhs) => {
+                self.operands_ty("%", lhs, rhs, is_numeric, "a number")
+            }
+            Expression::BitAndExpr(lhs, rhs) => {
+                self.operands_ty("&", lhs, rhs, is_integer, "an integer")
+            }
+            Expression::BitOrExpr(lhs, rhs) => {
+                self.operands_ty("|", lhs, rhs, is_integer, "an integer")
+            }
+            Expression::BitXorExpr(lhs, rhs) => {
+                self.operands_ty("^", lhs, rhs, is_integer, "an integer")
+            }
+            Expression::ShlExpr(lhs, rhs) => self.shift_ty("<<", lhs, rhs),
+            Expression::ShrExpr(lhs, rhs) => self.shift_ty(">>", lhs, rhs),
+            Expression::LtExpr(lhs, rhs) => {
+                self.operands_ty("<", lhs, rhs, is_ordered, "a number or `char`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::LteExpr(lhs, rhs) => {
+                self.operands_ty("<=", lhs, rhs, is_ordered, "a number or `char`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::GtExpr(lhs, rhs) => {
+                self.operands_ty(">", lhs, rhs, is_ordered, "a number or `char`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::GteExpr(lhs, rhs) => {
+                self.operands_ty(">=", lhs, rhs, is_ordered, "a number or `char`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::EqExpr(lhs, rhs) => {
+                self.equality_ty("==", lhs, rhs);
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::NeExpr(lhs, rhs) => {
+                self.equality_ty("!=", lhs, rhs);
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::AndExpr(lhs, rhs) => {
+                self.operands_ty("and", lhs, rhs, is_bool, "`bool`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::OrExpr(lhs, rhs) => {
+                self.operands_ty("or", lhs, rhs, is_bool, "`bool`");
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::NegExpr(inner, span) => {
+                let inner_ty = self.expr_ty(inner)?;
+                let inner_ty = self.resolve_ty(&inner_ty);
+                if !is_signed(&inner_ty) {
+                    self.diagnostics
+                        .push(negation_error(&inner_ty, inner.span()));
+                    return None;
+                }
+
+                // `-128` and `-(128)` fit in `s8` although `128` does not. Negated
+                // literals are range checked, other operands may still turn out
+                // unsigned.
+                match literal_operand(inner) {
+                    Some(token) => {
+                        if let Some(literal) = self
+                            .int_literals
+                            .last_mut()
+                            .filter(|literal| literal.span == token.span)
+                        {
+                            literal.negated = true;
+                            literal.span = *span;
+                        }
+                    }
+                    None if matches!(inner_ty, Type::Infer(_)) => {
+                        self.negations.push((inner_ty.clone(), inner.span()));
+                    }
+                    None => (),
+                }
+                Some(inner_ty)
+            }
+            Expression::NotExpr(inner, _) => {
+                let inner_ty = self.expr_ty(inner).map(|ty| self.resolve_ty(&ty));
+                if let Some(inner_ty) = inner_ty.filter(|ty| !is_bool(ty)) {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!(
+                            "cannot apply a logical not to type `{inner_ty}`"
+                        ))
+                        .with_code(codes::INVALID_OPERAND)
+                        .with_primary(inner.span(), "expected `bool`"),
+                    );
+                }
+                Some(Type::Prim(Primitive::Bool))
+            }
+            Expression::CastExpr { expr, ty, span } => self.cast_ty(expr, ty, *span),
+            Expression::BitNotExpr(inner, _) => {
+                let inner_ty = self.expr_ty(inner)?;
+                let inner_ty = self.resolve_ty(&inner_ty);
+                self.check_operand("~", inner, &inner_ty, is_integer, "an integer")
+                    .then_some(inner_ty)
+            }
+            Expression::CallExpr(callee, args, span) => self.call_ty(callee, args, *span),
+            Expression::FieldExpr(base, field) => self.field_ty(base, field),
+            Expression::IndexExpr(base, index, _) => {
+                let base_ty = self.expr_ty(base).map(|ty| self.resolve_ty(&ty));
+                self.expr_ty(index);
+                if let Some(base_ty) = base_ty {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!("cannot index into a value of type `{base_ty}`"))
+                            .with_code(codes::NOT_INDEXABLE)
+                            .with_primary(base.span(), format!("`{base_ty}` cannot be indexed")),
+                    );
+                }
+                None
+            }
+            Expression::IfExpr {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => self.if_ty(cond, then_branch, else_branch.as_deref()),
+            Expression::BlockExpr(block) => self.block_ty(block),
+            Expression::LoopExpr { label, body, .. } => {
+                // A loop that is never left has no value to give
+                let frame = self.loop_body_ty(label.as_ref(), LoopKind::Loop, body);
+                match frame.has_break {
+                    true => frame.break_ty,
+                    false => None,
+                }
+            }
+        }
+    }
+
+    // Locals have the type they were bound with, procs their signature
+    fn identifier_ty(&mut self, token: &Token) -> Option<Type> {
+        let id = self.symbols.resolve(token)?;
+        let decl = self.symbols.get(id);
+        match decl.kind {
+            DeclKind::Item(ItemKind::Proc) => self.items.sigs.get(&id).cloned(),
+            DeclKind::Item(kind @ (ItemKind::Struct | ItemKind::Enum)) => {
+                let kind = match kind {
+                    ItemKind::Struct => "struct",
+                    _ => "enum",
+                };
+                self.diagnostics.push(
+                    Diagnostic::error(format!(
+                        "expected a value, found {kind} `{}`",
+                        token.content
+                    ))
+                    .with_code(codes::NOT_A_VALUE)
+                    .with_primary(token.span, "not a value")
+                    .with_secondary(decl.span, format!("`{}` is declared here", token.content)),
+                );
+                None
+            }
+            _ => self.local_tys.get(&id).cloned(),
+        }
+    }
+
+    // Both operands of `op` must have the same type, accepted by `accepts`.
+    // Returns that type.
+    fn operands_ty(
+        &mut self,
+        op: &str,
+        lhs: &Expression,
+        rhs: &Expression,
+        accepts: fn(&Type) -> bool,
+        expected: &str,
+    ) -> Option<Type> {
+        let lhs_ty = self.expr_ty(lhs).map(|ty| self.resolve_ty(&ty));
+        let rhs_ty = self.expr_ty(rhs).map(|ty| self.resolve_ty(&ty));
+
+        let mut valid = true;
+        for (operand, ty) in [(lhs, &lhs_ty), (rhs, &rhs_ty)] {
+            if let Some(ty) = ty {
+                valid &= self.check_operand(op, operand, ty, accepts, expected);
+            }
+        }
+        if !valid {
+            // Literals in a rejected operation are not reported as ambiguous too
+            for ty in lhs_ty.iter().chain(&rhs_ty) {
+                self.settle(ty);
+            }
+            return None;
+        }
+
+        match (lhs_ty, rhs_ty) {
+            (Some(lhs_ty), Some(rhs_ty)) if !self.unify(&lhs_ty, &rhs_ty) => {
+                let (lhs_ty, rhs_ty) = self.mismatched(&lhs_ty, &rhs_ty);
+                let mut diagnostic = Diagnostic::error("mismatched types")
+                    .with_code(codes::MISMATCHED_TYPES)
+                    .with_primary(rhs.span(), format!("expected `{lhs_ty}`, found `{rhs_ty}`"))
+                    .with_secondary(lhs.span(), format!("this is `{lhs_ty}`"))
+                    .with_note(format!("both operands of `{op}` must have the same type"));
+
+                // Numbers are never converted implicitly, not even integers to floats
+                if is_numeric(&lhs_ty) && is_numeric(&rhs_ty) {
+                    diagnostic = diagnostic
+                        .with_help(format!("convert the right operand with `as {lhs_ty}`"));
+                }
+                self.diagnostics.push(diagnostic);
+                None
+            }
+            (lhs_ty, rhs_ty) => lhs_ty.or(rhs_ty),
+        }
+    }
+
+    // `==` and `!=` compare operands of the same primitive, string or enum
+    // type. Structs have no equality.
+    fn equality_ty(&mut self, op: &str, lhs: &Expression, rhs: &Expression) {
+        let Some(ty) = self.operands_ty(op, lhs, rhs, |_| true, "a value") else {
+            return;
+        };
+        let ty = self.resolve_ty(&ty);
+        let comparable = match &ty {
+            Type::Prim(_) | Type::Str | Type::Infer(_) => true,
+            Type::Named { module, name } => self
+                .symbols
+                .item(*module, name)
+                .is_some_and(|id| self.symbols.get(id).kind == DeclKind::Item(ItemKind::Enum)),
+            _ => false,
+        };
+        if !comparable {
+            self.diagnostics.push(
+                Diagnostic::error(format!("cannot apply `{op}` to type `{ty}`"))
+                    .with_code(codes::INVALID_OPERAND)
+                    .with_primary(
+                        lhs.span().to(rhs.span()),
+                        "expected a primitive, string or enum value",
+                    ),
+            );
+        }
+    }
+
+    // Type of `lhs << rhs` or `lhs >> rhs`. The shift amount may have any
+    // integer type, so only `lhs` gives the type of the result.
+    fn shift_ty(&mut self, op: &str, lhs: &Expression, rhs: &Expression) -> Option<Type> {
+        let lhs_ty = self.expr_ty(lhs).map(|ty| self.resolve_ty(&ty));
+        let rhs_ty = self.expr_ty(rhs).map(|ty| self.resolve_ty(&ty));
+
+        let mut valid = true;
+        for (operand, ty) in [(lhs, &lhs_ty), (rhs, &rhs_ty)] {
+            if let Some(ty) = ty {
+                valid &= self.check_operand(op, operand, ty, is_integer, "an integer");
+            }
+        }
+
+        // Nothing else constrains an unsuffixed shift amount
+        if let Some(rhs_ty) = &rhs_ty {
+            self.settle(rhs_ty);
+        }
+        if !valid {
+            if let Some(lhs_ty) = &lhs_ty {
+                self.settle(lhs_ty);
+            }
+            return None;
+        }
+
+        lhs_ty
+    }
+
+    // `expr as ty` converts between numbers, from `bool` and `char` to
+    // integers, and from `u8` to `char`
+    fn cast_ty(&mut self, expr: &Expression, ty: &TypeExpr, span: Span) -> Option<Type> {
+        let source = self.expr_ty(expr).map(|ty| self.resolve_ty(&ty));
+        let target = match eval_ty(ty, self.module, self.resolution) {
+            Ok(target) => target,
+            Err(diagnostic) => {
+                self.diagnostics.push(*diagnostic);
+                if let Some(source) = &source {
+                    self.settle(source);
+                }
+                return None;
+            }
+        };
+        let Some(source) = source else {
+            return Some(target);
+        };
+
+        // An unsuffixed integer cast to `char` is a code point, other literals
+        // being cast get their default type
+        let char_ty = Type::Prim(Primitive::Char);
+        if matches!(source, Type::Infer(var) if var.kind == InferKind::Int) && target == char_ty {
+            self.unify(&source, &Type::Prim(Primitive::U32));
+        }
+        self.settle(&source);
+        let source = self.resolve_ty(&source);
+
+        // `char` converts to and from `u8` and `u32` only
+        let valid = match (&source, &target) {
+            _ if source == target => true,
+            (Type::Prim(source), Type::Prim(target)) => match (source, target) {
+                (Primitive::U8 | Primitive::U32, Primitive::Char)
+                | (Primitive::Char, Primitive::U8 | Primitive::U32) => true,
+                (_, Primitive::Char) | (Primitive::Char, _) => false,
+                (Primitive::Bool, target) => target.is_integer(),
+                (source, target) => {
+                    (source.is_integer() || source.is_float())
+                        && (target.is_integer() || target.is_float())
+                }
+            },
+            _ => false,
+        };
+
+        if !valid {
+            let mut diagnostic = Diagnostic::error(format!("cannot cast `{source}` as `{target}`"))
+                .with_code(codes::INVALID_CAST)
+                .with_primary(span, "invalid cast");
+            if source == char_ty || target == char_ty {
+                diagnostic =
+                    diagnostic.with_note("`char` can only be cast to and from `u8` and `u32`");
+            }
+            self.diagnostics.push(diagnostic);
+            return Some(target);
+        }
+
+        // A `u32` that is not a Unicode scalar value has no `char`. Literals
+        // are checked here; other values are the programmer's responsibility.
+        if source == Type::Prim(Primitive::U32)
+            && target == char_ty
+            && let Some(token) = literal_operand(expr)
+            && let Some(value) = token.num_literal().and_then(|literal| literal.int_value())
+            && u32::try_from(value).is_ok_and(|value| char::from_u32(value).is_none())
+        {
+            self.diagnostics.push(
+                Diagnostic::error(format!("`{}` is not a valid `char`", token.content))
+                    .with_code(codes::INVALID_CAST)
+                    .with_primary(token.span, "not a Unicode scalar value")
+                    .with_note("a `char` is `0` to `0xD7FF` or `0xE000` to `0x10FFFF`"),
+            );
+        }
+
+        Some(target)
+    }
+
+    // Report `operand` if `op` does not accept its type
+    fn check_operand(
+        &mut self,
+        op: &str,
+        operand: &Expression,
+        ty: &Type,
+        accepts: fn(&Type) -> bool,
+        expected: &str,
+    ) -> bool {
+        if accepts(ty) {
+            return true;
+        }
+
+        self.diagnostics.push(
+            Diagnostic::error(format!("cannot apply `{op}` to type `{ty}`"))
+                .with_code(codes::INVALID_OPERAND)
+                .with_primary(operand.span(), format!("expected {expected}")),
+        );
+        false
+    }
+
+    // Arguments are checked against the parameters of the callee's signature
+    fn call_ty(&mut self, callee: &Expression, args: &[Expression], span: Span) -> Option<Type> {
+        let callee_ty = self.expr_ty(callee);
+        let arg_tys = args
+            .iter()
+            .map(|arg| self.expr_ty(arg))
+            .collect::<Vec<Option<Type>>>();
+
+        let (inputs, output) = match callee_ty? {
+            Type::Function { inputs, output } => (inputs, output),
+            callee_ty => {
+                self.diagnostics.push(
+                    Diagnostic::error(format!("expected a procedure, found `{callee_ty}`"))
+                        .with_code(codes::NOT_CALLABLE)
+                        .with_primary(callee.span(), "not a procedure"),
+                );
+                return None;
+            }
+        };
+
+        if let Some(inputs) = inputs {
+            if inputs.len() != args.len() {
+                self.diagnostics.push(
+                    Diagnostic::error(format!(
+                        "this procedure takes {} argument(s) but {} {} supplied",
+                        inputs.len(),
+                        args.len(),
+                        if args.len() == 1 { "was" } else { "were" }
+                    ))
+                    .with_code(codes::ARG_COUNT_MISMATCH)
+                    .with_primary(span, format!("expected {} argument(s)", inputs.len())),
+                );
+            }
+
+            for ((_, param_ty), (arg, arg_ty)) in inputs.iter().zip(args.iter().zip(&arg_tys)) {
+                self.expect_ty(param_ty, arg_ty.as_ref(), arg);
+            }
+        }
+
+        Some(output.map_or(Type::Unit, |output| *output))
+    }
+
+    // `value.field` on a struct, or `Enum.Variant`
+    fn field_ty(&mut self, base: &Expression, field: &Token) -> Option<Type> {
+        if let Expression::Atom {
+            inner: Atom::Identifier(token),
+        } = base
+            && let Some(id) = self.symbols.resolve(token)
+            && self.symbols.get(id).kind == DeclKind::Item(ItemKind::Enum)
+        {
+            return self.variant_ty(id, field);
+        }
+
+        let base_ty = self.expr_ty(base)?;
+        let base_ty = self.resolve_ty(&base_ty);
+        let fields = match &base_ty {
+            Type::Named { module, name } => self
+                .symbols
+                .item(*module, name)
+                .and_then(|id| self.items.fields.get(&id)),
+            _ => None,
+        };
+
+        let Some(field_ty) =
+            fields.and_then(|fields| fields.iter().find(|f| f.name == field.content))
+        else {
+            self.diagnostics.push(
+                Diagnostic::error(format!("no field `{}` on type `{base_ty}`", field.content))
+                    .with_code(codes::UNKNOWN_FIELD)
+                    .with_primary(field.span, "unknown field"),
+            );
+            return None;
+        };
+
+        // Private fields are only visible in the module defining the struct
+        if let Type::Named { module, name } = &base_ty
+            && field_ty.vis == Visibility::Private
+            && *module != self.module
+        {
+            self.diagnostics.push(
+                Diagnostic::error(format!(
+                    "field `{}` of struct `{name}` is private",
+                    field.content
+                ))
+                .with_code(codes::PRIVATE_ITEM)
+                .with_primary(field.span, "private field")
+                .with_help("declare the field with `pub` to access it from other modules"),
+            );
+        }
+
+        field_ty.ty.clone()
+    }
+
+    fn variant_ty(&mut self, enum_id: DeclId, variant: &Token) -> Option<Type> {
+        let decl = self.symbols.get(enum_id);
+        let found = self
+            .items
+            .variants
+            .get(&enum_id)?
+            .iter()
+            .find(|(_, name)| *name == variant.content);
+
+        match found {
+            Some((vis, _)) => {
+                if *vis == Visibility::Private && decl.module != self.module {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!(
+                            "variant `{}` of enum `{}` is private",
+                            variant.content, decl.name
+                        ))
+                        .with_code(codes::PRIVATE_ITEM)
+                        .with_primary(variant.span, "private variant"),
+                    );
+                }
+            }
+            None => {
+                self.diagnostics.push(
+                    Diagnostic::error(format!(
+                        "no variant `{}` in enum `{}`",
+                        variant.content, decl.name
+                    ))
+                    .with_code(codes::UNKNOWN_FIELD)
+                    .with_primary(variant.span, "unknown variant")
+                    .with_secondary(decl.span, format!("enum `{}` declared here", decl.name)),
+                );
+                return None;
+            }
+        }
+
+        Some(Type::Named {
+            module: decl.module,
+            name: decl.name.clone(),
+        })
+    }
+
+    // Both branches must agree on the type of an `if` used as a value
+    fn if_ty(
+        &mut self,
+        cond: &Expression,
+        then_branch: &Block,
+        else_branch: Option<&Expression>,
+    ) -> Option<Type> {
+        let cond_ty = self.expr_ty(cond);
+        self.expect_ty(&Type::Prim(Primitive::Bool), cond_ty.as_ref(), cond);
+
+        let then_ty = self.block_ty(then_branch);
+        let Some(else_branch) = else_branch else {
+            // Without `else` the `if` has no value, so neither may its branch
+            if let Some(then_ty) = then_ty
+                && !self.unify(&then_ty, &Type::Unit)
+            {
+                let (then_ty, _) = self.mismatched(&then_ty, &Type::Unit);
+                self.diagnostics.push(
+                    Diagnostic::error("`if` may be missing an `else` branch")
+                        .with_code(codes::MISMATCHED_TYPES)
+                        .with_primary(
+                            then_branch
+                                .expr
+                                .as_ref()
+                                .map_or(then_branch.span, |expr| expr.span()),
+                            format!("expected `()`, found `{then_ty}`"),
+                        )
+                        .with_note("an `if` without `else` evaluates to `()`")
+                        .with_help("add an `else` branch, or end the value with `;`"),
+                );
+            }
+            return Some(Type::Unit);
+        };
+        let else_ty = self.expr_ty(else_branch);
+
+        match (then_ty, else_ty) {
+            (Some(then_ty), Some(else_ty)) if !self.unify(&then_ty, &else_ty) => {
+                let (then_ty, else_ty) = self.mismatched(&then_ty, &else_ty);
+                self.diagnostics.push(
+                    Diagnostic::error("`if` and `else` have incompatible types")
+                        .with_code(codes::MISMATCHED_TYPES)
+                        .with_primary(
+                            branch_value_span(else_branch),
+                            format!("expected `{then_ty}`, found `{else_ty}`"),
+                        )
+                        .with_secondary(
+                            then_branch
+                                .expr
+                                .as_ref()
+                                .map_or(then_branch.span, |expr| expr.span()),
+                            "expected because of this",
+                        ),
+                );
+                None
+            }
+            (then_ty, else_ty) => then_ty.or(else_ty),
+        }
+    }
+
+    fn block_ty(&mut self, block: &Block) -> Option<Type> {
+        for statement in &block.body {
+            self.check_statement(statement);
+        }
+
+        match &block.expr {
+            Some(expr) => self.expr_ty(expr),
+
+            // A block left through a jump has no value of its own
+            None if matches!(
+                block.body.last(),
+                Some(
+                    Statement::Return { .. } | Statement::Break { .. } | Statement::Continue { .. }
+                )
+            ) =>
+            {
+                None
+            }
+            None => Some(Type::Unit),
+        }
+    }
+
+    // Suffixed literals have a type of their own (e.g. `2u8`), others are inferred
+    fn literal_ty(&mut self, token: &Token) -> Option<Type> {
+        let kind = match token.kind {
+            Some(TokenKind::StringLiteral) => return Some(Type::Str),
+            Some(TokenKind::CharLiteral) => return Some(Type::Prim(Primitive::Char)),
+            Some(TokenKind::BoolLiteral) => return Some(Type::Prim(Primitive::Bool)),
+            Some(TokenKind::IntLiteral) => InferKind::Int,
+            Some(TokenKind::FloatLiteral) => InferKind::Float,
+            _ => return None,
+        };
+
+        let ty = match num_suffix(token) {
+            None => {
+                let id = self.infer_slots.len();
+                self.infer_slots.push(InferSlot {
+                    kind,
+                    ty: None,
+                    defaulted: false,
+                });
+                Type::Infer(InferVar { id, kind })
+            }
+            Some(_) => match eval_ty_from_literal(token) {
+                Ok(ty) => ty,
+                Err(diagnostic) => {
+                    self.diagnostics.push(*diagnostic);
+                    return None;
+                }
+            },
+        };
+
+        match kind {
+            InferKind::Int => self.int_literals.push(IntLiteral {
+                content: token.content.clone(),
+                value: token.num_literal().and_then(|literal| literal.int_value()),
+                ty: ty.clone(),
+                negated: false,
+                span: token.span,
+            }),
+            InferKind::Float => self.float_literals.push(FloatLiteral {
+                content: token.content.clone(),
+                value: token
+                    .num_literal()
+                    .and_then(|literal| literal.float_value()),
+                ty: ty.clone(),
+                span: token.span,
+            }),
+        }
+        Some(ty)
+    }
+}
+
+fn is_numeric(ty: &Type) -> bool {
+    matches!(ty, Type::Prim(prim) if prim.is_integer() || prim.is_float())
+        || matches!(ty, Type::Infer(_))
+}
+
+// Numbers and chars can be compared with `<` and friends
+fn is_ordered(ty: &Type) -> bool {
+    is_numeric(ty) || *ty == Type::Prim(Primitive::Char)
+}
+
+fn is_integer(ty: &Type) -> bool {
+    match ty {
+        Type::Prim(prim) => prim.is_integer(),
+        Type::Infer(var) => var.kind == InferKind::Int,
+        _ => false,
+    }
+}
+
+fn negation_error(ty: &Type, operand: Span) -> Diagnostic {
+    let message = match is_numeric(ty) {
+        true => format!("cannot negate unsigned type `{ty}`"),
+        false => format!("cannot apply `-` to type `{ty}`"),
+    };
+    Diagnostic::error(message)
+        .with_code(codes::INVALID_OPERAND)
+        .with_primary(operand, "expected a signed integer or float")
+}
+
+// Literals whose type is not inferred yet may still become signed
+fn is_signed(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::Prim(
+            Primitive::S8
+                | Primitive::S16
+                | Primitive::S32
+                | Primitive::S64
+                | Primitive::F32
+                | Primitive::F64
+        ) | Type::Infer(_)
+    )
+}
+
+fn is_bool(ty: &Type) -> bool {
+    *ty == Type::Prim(Primitive::Bool)
+}
+
+// Literal an operand consists of, looking through parentheses
+fn literal_operand(expr: &Expression) -> Option<&Token> {
+    match expr {
+        Expression::Atom {
+            inner: Atom::Literal(token),
+        } => Some(token),
+        Expression::ParenExpr(inner, _) => literal_operand(inner),
+        _ => None,
+    }
+}
+
+// Span of the value a branch evaluates to (e.g. the tail expression of a block)
+fn branch_value_span(branch: &Expression) -> Span {
+    match branch {
+        Expression::BlockExpr(block) => block.expr.as_ref().map_or(block.span, |expr| expr.span()),
+        _ => branch.span(),
+    }
+}
+
+// Variable a place expression is rooted at (e.g. `p` in `p.pos.x`)
+fn place_root(expr: &Expression) -> Option<&Token> {
+    match expr {
+        Expression::Atom {
+            inner: Atom::Identifier(token),
+        } => Some(token),
+        Expression::FieldExpr(base, _) | Expression::IndexExpr(base, _, _) => place_root(base),
+        Expression::ParenExpr(inner, _) => place_root(inner),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diagnostics::codes;
+
+    use crate::tests::check_source;
+
+    #[test]
+    fn assigning_to_proc_is_rejected() {
+        let diagnostics = check_source(
+            "helper :: proc () -> s32 { 1 }
+            main :: proc () {
+                helper = 3;
+                helper += 1;
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::ASSIGN_TO_IMMUTABLE,
+                    "cannot assign to proc `helper`".to_string()
+                ),
+                (
+                    codes::ASSIGN_TO_IMMUTABLE,
+                    "cannot assign to proc `helper`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn assigning_requires_mut() {
+        let diagnostics = check_source(
+            "main :: proc (p :: s32) {
+                x := 1;
+                x = 2;
+                mut y := 1;
+                y = 2;
+                p = 3;
+                for i in 0..3 { i = 1; }
+            }",
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|(code, message)| {
+                assert_eq!(*code, codes::ASSIGN_TO_IMMUTABLE);
+                message.as_str()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            messages,
+            [
+                "cannot assign twice to immutable variable `x`",
+                "cannot assign to immutable parameter `p`",
+                "cannot assign to loop variable `i`",
+            ]
+        );
+    }
+
+    #[test]
+    fn if_branches_agree_on_a_type() {
+        let diagnostics = check_source(
+            "main :: proc (c :: bool, n :: s64) {
+                a :: s64 = if c { n } else { 1 };
+                b := if c { 1 } else if n > 2 { 2 } else { 3 };
+                if c { a; }
+                d := if c { 1 } else { true };
+                e := if n { 1 } else { 2 };
+                f :: s32 = if c { 1 };
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::MISMATCHED_TYPES,
+                    "`if` and `else` have incompatible types".to_string()
+                ),
+                (codes::MISMATCHED_TYPES, "mismatched types".to_string()),
+                (
+                    codes::MISMATCHED_TYPES,
+                    "`if` may be missing an `else` branch".to_string()
+                ),
+                (codes::MISMATCHED_TYPES, "mismatched types".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn labels_resolve_apart_from_locals() {
+        let diagnostics = check_source(
+            "main :: proc () {
+                outer := 1;
+                v :: s32 = 'outer :: loop {
+                    for i in 0..3 { break 'outer outer; }
+                    break 'nope;
+                };
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [(
+                codes::UNDECLARED_LABEL,
+                "use of undeclared label `'nope`".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn for_ranges_must_be_integers() {
+        let diagnostics = check_source(
+            "Point :: struct { x :: s32 }
+            main :: proc (n :: u64, p :: Point, b :: bool) {
+                for i in 0..10 {}
+                for j in 0..n {}
+                for k in p..p {}
+                for l in 1.5..3.0 {}
+                for m in b..b {}
+            }",
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|(code, message)| {
+                assert_eq!(*code, codes::MISMATCHED_TYPES);
+                message.as_str()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(messages, ["mismatched types"; 3]);
+    }
+
+    #[test]
+    fn only_enums_and_primitives_compare() {
+        let diagnostics = check_source(
+            "Point :: struct { x :: s32 }
+            Color :: enum { Red, Green }
+            same :: proc (a :: Point, b :: Point, c :: Color) -> bool {
+                a == b or a != b or c == Color.Red or 1 != 2
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `==` to type `Point`".to_string()
+                ),
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `!=` to type `Point`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn bit_operators_take_integers() {
+        let diagnostics = check_source(
+            "main :: proc (a :: u8, b :: u32, c :: bool) {
+                mut d := a & 0xf0 | a ^ ~a;
+                d <<= b;
+                e :: u8 = a >> 1 % 3;
+                f := c | c;
+                g := a & b;
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `|` to type `bool`".to_string()
+                ),
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `|` to type `bool`".to_string()
+                ),
+                (codes::MISMATCHED_TYPES, "mismatched types".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn negating_an_inferred_unsigned_value_is_rejected() {
+        let diagnostics = check_source(
+            "main :: proc () {
+                a := 5;
+                b :: u32 = -a;
+                c := 7;
+                d :: s64 = -c;
+                e :: u8 = -(4);
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot negate unsigned type `u32`".to_string()
+                ),
+                (
+                    codes::LITERAL_OUT_OF_RANGE,
+                    "literal out of range for `u8`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn unconstrained_literals_default_silently() {
+        let diagnostics = check_source(
+            "main :: proc () {
+                x := 5;
+                mut q := 1;
+                q = 6;
+                f := 1.5;
+                for i in 0..10 {}
+            }",
+        );
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn defaulted_literal_out_of_range_is_explained() {
+        let (module_manager, ast) = crate::tests::parse(
+            "main :: proc () {
+                x := 3000000000;
+            }",
+        );
+        let diagnostics = crate::check(&ast, &module_manager);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, Some(codes::LITERAL_OUT_OF_RANGE));
+        assert!(
+            diagnostics[0]
+                .notes
+                .iter()
+                .any(|note| note.contains("defaults to `s32`")),
+            "{:?}",
+            diagnostics[0].notes
+        );
+    }
+
+    #[test]
+    fn negated_literals_are_range_checked() {
+        let diagnostics = check_source(
+            "main :: proc () {
+                a :: s8 = -128;
+                b :: s8 = -(128);
+                c :: s8 = -((128));
+                d :: s8 = -(129);
+                e :: s8 = 128;
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    codes::LITERAL_OUT_OF_RANGE,
+                    "literal out of range for `s8`".to_string()
+                ),
+                (
+                    codes::LITERAL_OUT_OF_RANGE,
+                    "literal out of range for `s8`".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn char_casts_to_and_from_u8_and_u32() {
+        let diagnostics = check_source(
+            "main :: proc (b :: u8, w :: u32, c :: char) {
+                a := b as char;
+                d := w as char;
+                e := 0x1F48E as char;
+                f := c as u8;
+                g := c as u32;
+            }",
+        );
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn other_char_casts_are_rejected() {
+        let diagnostics = check_source(
+            "main :: proc (x :: s32, h :: u16, c :: char) {
+                a := x as char;
+                b := h as char;
+                d := c as s8;
+                e := c as u16;
+                f := c as s64;
+                g := 0x110000 as char;
+                i := 0xD800 as char;
+            }",
+        );
+        let messages = diagnostics
+            .iter()
+            .map(|(code, message)| {
+                assert_eq!(*code, codes::INVALID_CAST);
+                message.as_str()
+            })
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            messages,
+            [
+                "cannot cast `s32` as `char`",
+                "cannot cast `u16` as `char`",
+                "cannot cast `char` as `s8`",
+                "cannot cast `char` as `u16`",
+                "cannot cast `char` as `s64`",
+                "`0x110000` is not a valid `char`",
+                "`0xD800` is not a valid `char`",
+            ]
+        );
+    }
+
+    #[test]
+    fn float_types_and_literals() {
+        let diagnostics = check_source(
+            "Vec2 :: struct { x :: f32, y :: f64 }
+            scale :: proc (v :: Vec2, k :: f64) -> f64 {
+                a := (v.x as f64) * k + 0.5;
+                b :: f32 = v.x / 2.0 - 1e3;
+                c :: f32 = 3.4e38;
+                d := -2.5e-3 * a;
+                e := v.x < 1.0 and k >= 2.0;
+                a % k
+            }",
+        );
+        assert_eq!(diagnostics, []);
+    }
+
+    #[test]
+    fn float_range_and_mixed_arithmetic_are_rejected() {
+        let diagnostics = check_source(
+            "main :: proc (x :: f32, n :: s32) {
+                a :: f32 = 1e39;
+                b := 1e400;
+                c := n * 2.0;
+                d := x + n;
+                e := 1 + 1.5;
+                f :: f64 = x;
+                g := x < 2;
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            std::iter::repeat_n((codes::MISMATCHED_TYPES, "mismatched types".to_string()), 5)
+                .chain([
+                    (
+                        codes::LITERAL_OUT_OF_RANGE,
+                        "literal out of range for `f32`".to_string()
+                    ),
+                    (
+                        codes::LITERAL_OUT_OF_RANGE,
+                        "literal out of range for `f64`".to_string()
+                    ),
+                ])
+                .collect::<Vec<(&str, String)>>()
+        );
+    }
+
+    #[test]
+    fn logical_operators_take_and_give_bool() {
+        let diagnostics = check_source(
+            "main :: proc (n :: s32, s :: str) {
+                a :: bool = n > 1 and true;
+                b :: bool = not a or false;
+                c :: s32 = a or b;
+                d := n and true;
+                e := false or s;
+            }",
+        );
+        assert_eq!(
+            diagnostics,
+            [
+                (codes::MISMATCHED_TYPES, "mismatched types".to_string()),
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `and` to type `s32`".to_string()
+                ),
+                (
+                    codes::INVALID_OPERAND,
+                    "cannot apply `or` to type `str`".to_string()
+                ),
+            ]
+        );
+    }
+}
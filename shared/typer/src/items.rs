@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use diagnostics::Diagnostic;
+use parser::{Ast, DeclarationDef, Visibility};
+
+use crate::symbols::{DeclId, SymbolTable};
+use crate::{Resolution, Type, eval_ty};
+
+/// Field of a struct with its evaluated type.
+#[derive(Debug, Clone)]
+pub struct FieldTy {
+    pub vis: Visibility,
+    pub name: String,
+
+    // `None` if the written type could not be evaluated
+    pub ty: Option<Type>,
+}
+
+/// Types of the top-level items, shared by the checks of every proc body.
+#[derive(Debug, Default)]
+pub struct ItemTypes {
+    // Signature of every item (`Type::Function` for procs)
+    pub sigs: HashMap<DeclId, Type>,
+    pub fields: HashMap<DeclId, Vec<FieldTy>>,
+    pub variants: HashMap<DeclId, Vec<(Visibility, String)>>,
+}
+
+impl ItemTypes {
+    /// Evaluate the signature of every item and the types of struct fields.
+    /// Types that cannot be evaluated are reported and left out.
+    pub fn collect(
+        ast: &Ast,
+        resolution: &Resolution,
+        symbols: &SymbolTable,
+    ) -> (ItemTypes, Vec<Diagnostic>) {
+        let mut items = ItemTypes::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+        for &module_id in &resolution.order {
+            let Some(module) = ast.program.modules.iter().find(|m| m.id == module_id) else {
+                continue;
+            };
+
+            for declaration in &module.declarations {
+                // Duplicate definitions are not in the symbol table
+                let id = symbols.resolve(&declaration.sig.name);
+
+                match eval_ty(&declaration.sig.ty, module_id, resolution) {
+                    Ok(ty) => {
+                        if let Some(id) = id {
+                            items.sigs.insert(id, ty);
+                        }
+                    }
+                    Err(diagnostic) => diagnostics.push(*diagnostic),
+                }
+
+                match &declaration.def {
+                    DeclarationDef::Struct { fields } => {
+                        let mut field_tys: Vec<FieldTy> = Vec::new();
+                        for field in fields {
+                            let ty = match eval_ty(&field.ty, module_id, resolution) {
+                                Ok(ty) => Some(ty),
+                                Err(diagnostic) => {
+                                    diagnostics.push(*diagnostic);
+                                    None
+                                }
+                            };
+
+                            field_tys.push(FieldTy {
+                                vis: field.vis,
+                                name: field.name.content.clone(),
+                                ty,
+                            });
+                        }
+
+                        if let Some(id) = id {
+                            items.fields.insert(id, field_tys);
+                        }
+                    }
+                    DeclarationDef::Enum { variants } => {
+                        if let Some(id) = id {
+                            let variants = variants
+                                .iter()
+                                .map(|variant| (variant.vis, variant.name.content.clone()))
+                                .collect();
+                            items.variants.insert(id, variants);
+                        }
+                    }
+                    DeclarationDef::Function { .. } => (),
+                }
+            }
+        }
+
+        (items, diagnostics)
+    }
+}
@@ -1,35 +1,79 @@
-#![feature(assert_matches)]
+mod body;
+mod items;
+mod resolve;
+mod symbols;
 
-use std::assert_matches::assert_matches;
+use std::fmt::Display;
 
-use lexical_analyzer::{
-    ModuleTokenStream, OperatorKind::TypeQualifier, PunctuationKind, ReservedKind, Token, TokenKind,
-};
+use body::BodyChecker;
+use diagnostics::{Diagnostic, codes};
+pub use items::{FieldTy, ItemTypes};
+use lexical_analyzer::{NumSuffix, ReservedKind, ScannerPrimKind, Token, TokenKind};
+use module_manager::ModuleManager;
+use parser::{Ast, DeclarationDef, TypeExpr};
+pub use resolve::{Item, ItemKind, ModuleScope, Resolution, resolve_program};
+use span::FileId;
+pub use symbols::{Decl, DeclId, DeclKind, SymbolTable, resolve_names};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Prim(Primitive),
+
+    // Value of statements and blocks without a tail expression
+    Unit,
+
+    // Text, e.g. a string literal
+    Str,
     Struct,
     Enum,
     Function {
         inputs: Option<Vec<(String, Type)>>,
         output: Option<Box<Type>>,
     },
+
+    // User-defined struct or enum, identified by the module defining it
+    Named {
+        module: FileId,
+        name: String,
+    },
+
+    // Type of an unsuffixed literal, inferred while checking a proc body
+    Infer(InferVar),
 }
 
-impl Type {
-    fn push_input(&mut self, parameter_name: String, parameter_type: Type) {
-        assert_matches!(self, Type::Function { .. });
+/// Type variable standing for the type of an unsuffixed literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InferVar {
+    pub id: usize,
+    pub kind: InferKind,
+}
 
-        if let Type::Function { inputs, .. } = self {
-            if let Some(inputs) = inputs {
-                inputs.push((parameter_name, parameter_type));
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferKind {
+    // `5`: any integer type
+    Int,
+
+    // `5.0`: `f32` or `f64`
+    Float,
+}
+
+impl InferKind {
+    /// Whether a literal of this kind can have type `ty`.
+    pub fn accepts(self, ty: &Type) -> bool {
+        match (self, ty) {
+            (InferKind::Int, Type::Prim(prim)) => prim.is_integer(),
+            (InferKind::Float, Type::Prim(prim)) => prim.is_float(),
+            (_, Type::Infer(var)) => var.kind == self,
+            _ => false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+// Types of literals nothing constrains (e.g. `x := 5;`)
+pub const DEFAULT_INT: Primitive = Primitive::S32;
+pub const DEFAULT_FLOAT: Primitive = Primitive::F64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Primitive {
     Bool,
     U8,
@@ -42,171 +86,359 @@ pub enum Primitive {
     S64,
     F32,
     F64,
+    Char,
 }
 
-pub fn eval_ty_from_token(token: Token, token_stream: Option<&mut ModuleTokenStream>) -> Type {
-    match token.kind {
-        Some(kind) => match kind {
-            lexical_analyzer::TokenKind::IntLiteral => eval_int_ty_from_literal(&token),
-            lexical_analyzer::TokenKind::FloatLiteral => eval_float_ty_from_literal(&token),
-            lexical_analyzer::TokenKind::Identifier => {
-                todo!(
-                    "Need to implement global symbol table first to resolve identifier: `{identifier:?}`.",
-                    identifier = token.content
-                )
-            }
-            lexical_analyzer::TokenKind::Reserved(reserved_kind) => {
-                eval_ty_from_reserved_word(reserved_kind, token_stream)
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Prim(prim) => write!(f, "{prim}"),
+            Type::Unit => write!(f, "()"),
+            Type::Str => write!(f, "str"),
+            Type::Struct => write!(f, "struct"),
+            Type::Enum => write!(f, "enum"),
+            Type::Function { inputs, output } => {
+                write!(f, "proc (")?;
+                for (i, (name, ty)) in inputs.iter().flatten().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name} :: {ty}")?;
+                }
+                write!(f, ")")?;
+                match output {
+                    Some(output) => write!(f, " -> {output}"),
+                    None => Ok(()),
+                }
             }
-            lexical_analyzer::TokenKind::Punctuation(punctuation_kind) => {
-                panic!("Punctuation: `{punctuation_kind:?}` does not have a type to evaluate.")
-            }
-            lexical_analyzer::TokenKind::Operator(operator_kind) => {
-                todo!("Evaluating operators as types not implemented.")
+            Type::Named { name, .. } => write!(f, "{name}"),
+            Type::Infer(var) => match var.kind {
+                InferKind::Int => write!(f, "{{integer}}"),
+                InferKind::Float => write!(f, "{{float}}"),
+            },
+        }
+    }
+}
+
+impl Primitive {
+    pub fn is_integer(self) -> bool {
+        !matches!(
+            self,
+            Primitive::Bool | Primitive::F32 | Primitive::F64 | Primitive::Char
+        )
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, Primitive::F32 | Primitive::F64)
+    }
+
+    /// Smallest and largest value of an integer type.
+    pub fn int_range(self) -> Option<(i128, i128)> {
+        let range = match self {
+            Primitive::U8 => (0, u8::MAX as i128),
+            Primitive::U16 => (0, u16::MAX as i128),
+            Primitive::U32 => (0, u32::MAX as i128),
+            Primitive::U64 => (0, u64::MAX as i128),
+            Primitive::S8 => (i8::MIN as i128, i8::MAX as i128),
+            Primitive::S16 => (i16::MIN as i128, i16::MAX as i128),
+            Primitive::S32 => (i32::MIN as i128, i32::MAX as i128),
+            Primitive::S64 => (i64::MIN as i128, i64::MAX as i128),
+            Primitive::Bool | Primitive::F32 | Primitive::F64 | Primitive::Char => return None,
+        };
+
+        Some(range)
+    }
+}
+
+impl Display for Primitive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prim = match self {
+            Primitive::Bool => "bool",
+            Primitive::U8 => "u8",
+            Primitive::U16 => "u16",
+            Primitive::U32 => "u32",
+            Primitive::U64 => "u64",
+            Primitive::S8 => "s8",
+            Primitive::S16 => "s16",
+            Primitive::S32 => "s32",
+            Primitive::S64 => "s64",
+            Primitive::F32 => "f32",
+            Primitive::F64 => "f64",
+            Primitive::Char => "char",
+        };
+
+        write!(f, "{prim}")
+    }
+}
+
+/// Resolve imports, the names used in proc bodies and the types named in
+/// every declaration, then type check proc bodies. Modules are checked in dependency order.
+pub fn check(ast: &Ast, module_manager: &ModuleManager) -> Vec<Diagnostic> {
+    let (resolution, mut diagnostics) = resolve_program(ast, module_manager);
+    let (symbols, name_diagnostics) = resolve_names(ast, &resolution);
+    diagnostics.extend(name_diagnostics);
+
+    // Every signature is known before any body is checked, so procs can call
+    // procs declared after them
+    let (items, item_diagnostics) = ItemTypes::collect(ast, &resolution, &symbols);
+    diagnostics.extend(item_diagnostics);
+
+    for &module_id in &resolution.order {
+        let Some(module) = ast.program.modules.iter().find(|m| m.id == module_id) else {
+            continue;
+        };
+
+        for declaration in &module.declarations {
+            if let (DeclarationDef::Function { def }, TypeExpr::Proc { params, output }) =
+                (&declaration.def, &declaration.sig.ty)
+            {
+                let checker = BodyChecker::new(module_id, &resolution, &symbols, &items);
+                diagnostics.extend(checker.check_function(params, output.as_deref(), def));
             }
-        },
-        None => panic!("Got `None` `token.kind`. This is likely an error in the scanner."),
-    }
-}
-
-fn eval_ty_from_reserved_word(
-    reserved_kind: ReservedKind,
-    mut token_stream: Option<&mut ModuleTokenStream>,
-) -> Type {
-    match reserved_kind {
-        ReservedKind::Struct => Type::Struct,
-        ReservedKind::Enum => Type::Enum,
-        ReservedKind::PrimTy(prim_ty) => match prim_ty {
-            lexical_analyzer::ScannerPrimKind::Bool => Type::Prim(Primitive::Bool),
-            lexical_analyzer::ScannerPrimKind::S8 => Type::Prim(Primitive::S8),
-            lexical_analyzer::ScannerPrimKind::S16 => Type::Prim(Primitive::S16),
-            lexical_analyzer::ScannerPrimKind::S32 => Type::Prim(Primitive::S32),
-            lexical_analyzer::ScannerPrimKind::S64 => Type::Prim(Primitive::S64),
-            lexical_analyzer::ScannerPrimKind::U8 => Type::Prim(Primitive::U8),
-            lexical_analyzer::ScannerPrimKind::U16 => Type::Prim(Primitive::U16),
-            lexical_analyzer::ScannerPrimKind::U32 => Type::Prim(Primitive::U32),
-            lexical_analyzer::ScannerPrimKind::U64 => Type::Prim(Primitive::U64),
-        },
-        ReservedKind::Proc => {
-            assert!(
-                token_stream.is_some(),
-                "Token stream is required to evaluate proc type."
-            );
-            eval_proc_ty(token_stream.as_mut().unwrap())
         }
-        _ => panic!("Reserved word `{reserved_kind:?}` has no type to evaluate."),
     }
+
+    diagnostics
 }
 
-fn eval_proc_ty(token_stream: &mut ModuleTokenStream<'_>) -> Type {
-    let l_parn = token_stream.get_token().unwrap();
-    l_parn.assert_kind(TokenKind::Punctuation(PunctuationKind::OpenParen));
+/// Evaluate a type written in `module`, looking up named types in its scope.
+pub fn eval_ty(
+    ty: &TypeExpr,
+    module: FileId,
+    resolution: &Resolution,
+) -> Result<Type, Box<Diagnostic>> {
+    match ty {
+        TypeExpr::Prim(token) => eval_prim_ty(token),
+        TypeExpr::Named(token) => eval_named_ty(token, module, resolution),
+        TypeExpr::Struct => Ok(Type::Struct),
+        TypeExpr::Enum => Ok(Type::Enum),
+        TypeExpr::Proc { params, output } => {
+            let inputs = params
+                .iter()
+                .map(|param| {
+                    eval_ty(&param.ty, module, resolution)
+                        .map(|param_ty| (param.name.content.clone(), param_ty))
+                })
+                .collect::<Result<Vec<(String, Type)>, Box<Diagnostic>>>()?;
+
+            let output = match output {
+                Some(output) => Some(Box::new(eval_ty(output, module, resolution)?)),
+                None => None,
+            };
 
-    let mut function_type = Type::Function {
-        inputs: None,
-        output: None,
+            Ok(Type::Function {
+                inputs: Some(inputs),
+                output,
+            })
+        }
+    }
+}
+
+/// Type of an integer or float literal, taken from its suffix (e.g. `2u8`).
+pub fn eval_ty_from_literal(token: &Token) -> Result<Type, Box<Diagnostic>> {
+    match token.kind {
+        Some(TokenKind::IntLiteral) => eval_int_ty_from_literal(token),
+        Some(TokenKind::FloatLiteral) => eval_float_ty_from_literal(token),
+        _ => Err(Box::new(not_a_type(token))),
+    }
+}
+
+fn not_a_type(token: &Token) -> Diagnostic {
+    Diagnostic::error(format!("expected a type, found {}", token.describe()))
+        .with_code(codes::NOT_A_TYPE)
+        .with_primary(token.span, "expected a type")
+}
+
+fn eval_prim_ty(token: &Token) -> Result<Type, Box<Diagnostic>> {
+    let Some(TokenKind::Reserved(ReservedKind::PrimTy(prim_ty))) = token.kind else {
+        return Err(Box::new(not_a_type(token)));
     };
 
-    let current_token = token_stream.get_token().unwrap();
-    while current_token.kind != Some(TokenKind::Punctuation(PunctuationKind::CloseParen)) {
-        current_token.assert_kind(TokenKind::Identifier);
-        // PERF: Remove clone somehow.
-        let parameter_name = String::from(current_token.content.clone());
+    Ok(match prim_ty {
+        ScannerPrimKind::Bool => Type::Prim(Primitive::Bool),
+        ScannerPrimKind::S8 => Type::Prim(Primitive::S8),
+        ScannerPrimKind::S16 => Type::Prim(Primitive::S16),
+        ScannerPrimKind::S32 => Type::Prim(Primitive::S32),
+        ScannerPrimKind::S64 => Type::Prim(Primitive::S64),
+        ScannerPrimKind::U8 => Type::Prim(Primitive::U8),
+        ScannerPrimKind::U16 => Type::Prim(Primitive::U16),
+        ScannerPrimKind::U32 => Type::Prim(Primitive::U32),
+        ScannerPrimKind::U64 => Type::Prim(Primitive::U64),
+        ScannerPrimKind::F32 => Type::Prim(Primitive::F32),
+        ScannerPrimKind::F64 => Type::Prim(Primitive::F64),
+        ScannerPrimKind::Char => Type::Prim(Primitive::Char),
+        ScannerPrimKind::Str => Type::Str,
+    })
+}
 
-        let ty_punc = token_stream.get_token().unwrap();
-        ty_punc.assert_kind(TokenKind::Operator(TypeQualifier));
+fn eval_named_ty(
+    token: &Token,
+    module: FileId,
+    resolution: &Resolution,
+) -> Result<Type, Box<Diagnostic>> {
+    let name = token.content.as_str();
+    match resolution.lookup(module, name).as_slice() {
+        [] => {
+            if let Some(item) = resolution.lookup_private(module, name).first() {
+                return Err(Box::new(resolve::private_item(token, item)));
+            }
 
-        let next_token = token_stream.get_token().unwrap();
-        let parameter_type = eval_ty_from_token(next_token, Some(token_stream));
+            let mut diagnostic = Diagnostic::error(format!("cannot resolve type `{name}`"))
+                .with_code(codes::UNRESOLVED_TYPE)
+                .with_primary(token.span, "not found in this module or its imports");
 
-        function_type.push_input(parameter_name, parameter_type);
+            // Point at modules that would make the name visible if imported
+            for item in resolution.defined_anywhere(name) {
+                diagnostic = diagnostic.with_help(format!(
+                    "`{name}` is defined in module `{}`; add `import {};`",
+                    item.module_name, item.module_name
+                ));
+            }
 
-        // Optional comma. Eat if found.
-        if let Some(comma) = token_stream.get_token() {
-            comma.assert_kind(TokenKind::Punctuation(PunctuationKind::Comma));
+            Err(Box::new(diagnostic))
         }
+        [item] => match item.kind {
+            ItemKind::Struct | ItemKind::Enum => Ok(Type::Named {
+                module: item.module,
+                name: item.name.clone(),
+            }),
+            ItemKind::Proc => Err(Box::new(
+                not_a_type(token).with_secondary(item.span, format!("`{name}` is a procedure")),
+            )),
+        },
+        items => Err(Box::new(resolve::ambiguous_name(token, items))),
     }
-
-    function_type
 }
 
-fn eval_int_ty_from_literal(token: &Token) -> Type {
-    let int_literal_postfix = get_postfix_from_num_literal(token);
-    match int_literal_postfix {
-        Some(postfix) => match postfix {
-            NumPostfix::U8 => Type::Prim(Primitive::U8),
-            NumPostfix::U16 => Type::Prim(Primitive::U16),
-            NumPostfix::U32 => Type::Prim(Primitive::U32),
-            NumPostfix::U64 => Type::Prim(Primitive::U64),
-            NumPostfix::S8 => Type::Prim(Primitive::S8),
-            NumPostfix::S16 => Type::Prim(Primitive::S16),
-            NumPostfix::S32 => Type::Prim(Primitive::S32),
-            NumPostfix::S64 => Type::Prim(Primitive::S64),
-            _ => panic!("Unsupported postfix for int literal: `{}`", token.content),
+fn eval_int_ty_from_literal(token: &Token) -> Result<Type, Box<Diagnostic>> {
+    match num_suffix(token) {
+        Some(suffix) => match suffix {
+            NumSuffix::U8 => Ok(Type::Prim(Primitive::U8)),
+            NumSuffix::U16 => Ok(Type::Prim(Primitive::U16)),
+            NumSuffix::U32 => Ok(Type::Prim(Primitive::U32)),
+            NumSuffix::U64 => Ok(Type::Prim(Primitive::U64)),
+            NumSuffix::S8 => Ok(Type::Prim(Primitive::S8)),
+            NumSuffix::S16 => Ok(Type::Prim(Primitive::S16)),
+            NumSuffix::S32 => Ok(Type::Prim(Primitive::S32)),
+            NumSuffix::S64 => Ok(Type::Prim(Primitive::S64)),
+            _ => Err(Box::new(invalid_literal_suffix(token, "integer"))),
         },
-        None => Type::Prim(Primitive::U32),
+        None => Ok(Type::Prim(DEFAULT_INT)),
     }
 }
 
-fn eval_float_ty_from_literal(token: &Token) -> Type {
-    let float_literal_postfix = get_postfix_from_num_literal(token);
-    match float_literal_postfix {
-        Some(postfix) => match postfix {
-            NumPostfix::F32 => Type::Prim(Primitive::F32),
-            NumPostfix::F64 => Type::Prim(Primitive::F64),
-            _ => panic!("Unsupported postfix for float literal: `{}`", token.content),
+fn eval_float_ty_from_literal(token: &Token) -> Result<Type, Box<Diagnostic>> {
+    match num_suffix(token) {
+        Some(suffix) => match suffix {
+            NumSuffix::F32 => Ok(Type::Prim(Primitive::F32)),
+            NumSuffix::F64 => Ok(Type::Prim(Primitive::F64)),
+            _ => Err(Box::new(invalid_literal_suffix(token, "float"))),
         },
-        None => Type::Prim(Primitive::F32),
+        None => Ok(Type::Prim(DEFAULT_FLOAT)),
     }
 }
 
-#[derive(Debug)]
-enum NumPostfix {
-    U8,
-    U16,
-    U32,
-    U64,
-    S8,
-    S16,
-    S32,
-    S64,
-    F32,
-    F64,
+// Suffix the scanner found on a number literal
+fn num_suffix(token: &Token) -> Option<NumSuffix> {
+    token.num_literal().and_then(|literal| literal.suffix)
 }
 
-fn get_postfix_from_num_literal(token: &Token) -> Option<NumPostfix> {
-    // Postfix notation only supported for int and float literals
-    assert!(
-        matches!(
-            token.kind,
-            Some(TokenKind::IntLiteral | TokenKind::FloatLiteral),
-        ),
-        "Expected IntLiteral or FloatLiteral"
-    );
-
-    let token_raw = token.content.as_str();
-
-    if token_raw.contains("u8") {
-        return Some(NumPostfix::U8);
-    } else if token_raw.contains("u16") {
-        return Some(NumPostfix::U16);
-    } else if token_raw.contains("u32") {
-        return Some(NumPostfix::U32);
-    } else if token_raw.contains("u64") {
-        return Some(NumPostfix::U64);
-    } else if token_raw.contains("s8") {
-        return Some(NumPostfix::S8);
-    } else if token_raw.contains("s16") {
-        return Some(NumPostfix::S16);
-    } else if token_raw.contains("s32") {
-        return Some(NumPostfix::S32);
-    } else if token_raw.contains("s64") {
-        return Some(NumPostfix::S64);
-    } else if token_raw.contains("f32") {
-        return Some(NumPostfix::F32);
-    } else if token_raw.contains("f64") {
-        return Some(NumPostfix::F64);
-    } else {
-        None
+fn invalid_literal_suffix(token: &Token, literal_kind: &str) -> Diagnostic {
+    Diagnostic::error(format!(
+        "invalid suffix for {literal_kind} literal `{}`",
+        token.content
+    ))
+    .with_code(codes::INVALID_LITERAL_SUFFIX)
+    .with_primary(token.span, format!("not a valid {literal_kind} suffix"))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+
+    use lexical_analyzer::Scanner;
+
+    use super::*;
+
+    /// Scan and parse `src` as the module `main`. It must be free of syntax errors.
+    pub(crate) fn parse(src: &str) -> (ModuleManager, Ast) {
+        let module_manager =
+            ModuleManager::from_sources([(PathBuf::from("main.gem"), src.to_string())]);
+        let (token_streams, scanner_errors) = Scanner::new(&module_manager).scan();
+        assert!(scanner_errors.is_empty(), "{scanner_errors:?}");
+
+        let (ast, diagnostics) = Ast::new(token_streams);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        (module_manager, ast)
+    }
+
+    /// Code and message of every diagnostic reported for `src`.
+    pub(crate) fn check_source(src: &str) -> Vec<(&'static str, String)> {
+        check_sources(&[("main.gem", src)])
+    }
+
+    /// Code and message of every diagnostic reported for the modules `sources`.
+    pub(crate) fn check_sources(sources: &[(&str, &str)]) -> Vec<(&'static str, String)> {
+        let module_manager = ModuleManager::from_sources(
+            sources
+                .iter()
+                .map(|&(path, src)| (PathBuf::from(path), src.to_string())),
+        );
+        let (token_streams, scanner_errors) = Scanner::new(&module_manager).scan();
+        assert!(scanner_errors.is_empty(), "{scanner_errors:?}");
+
+        let (ast, diagnostics) = Ast::new(token_streams);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        check(&ast, &module_manager)
+            .into_iter()
+            .map(|diagnostic| {
+                let code = diagnostic.code.expect("Every diagnostic has a code");
+                (code, diagnostic.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unknown_return_types_are_rejected() {
+        assert_eq!(
+            check_source("main :: proc () -> number { 2 }"),
+            [(
+                codes::UNRESOLVED_TYPE,
+                "cannot resolve type `number`".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn only_pub_items_fields_and_variants_cross_modules() {
+        let util = "
+            pub Point :: struct { pub x :: s32, y :: s32 }
+            Hidden :: struct { }
+            pub Color :: enum { pub Red, Green }
+            secret :: proc () -> s32 { 1 }
+            pub reveal :: proc (p :: Point) -> s32 {
+                c := Color.Green;
+                p.y + secret()
+            }";
+        let main = "
+            import util;
+            main :: proc (p :: Point, h :: Hidden) -> s32 {
+                a := Color.Red;
+                b := Color.Green;
+                p.x + p.y + secret() + reveal(p)
+            }";
+
+        // Private items are only reported where `main` uses them
+        let private = |message: &str| (codes::PRIVATE_ITEM, message.to_string());
+        assert_eq!(
+            check_sources(&[("util.gem", util), ("main.gem", main)]),
+            [
+                private("`secret` is private to module `util`"),
+                private("`Hidden` is private to module `util`"),
+                private("variant `Green` of enum `Color` is private"),
+                private("field `y` of struct `Point` is private"),
+            ]
+        );
     }
 }
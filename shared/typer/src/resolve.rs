@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use diagnostics::{Diagnostic, codes};
+use lexical_analyzer::Token;
+use module_manager::{ModuleGraph, ModuleManager};
+use parser::{Ast, TypeExpr, Visibility};
+use span::{FileId, Span};
+
+/// Top-level declaration that can be referred to by name.
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub vis: Visibility,
+    pub name: String,
+    pub kind: ItemKind,
+    pub module: FileId,
+    pub module_name: String,
+
+    // Span of the declaration's name
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Struct,
+    Enum,
+    Proc,
+}
+
+/// Items defined in a module and the modules it imports.
+#[derive(Debug, Default)]
+pub struct ModuleScope {
+    pub items: BTreeMap<String, Item>,
+    pub imports: Vec<FileId>,
+}
+
+/// Result of resolving every module's imports.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    pub scopes: BTreeMap<FileId, ModuleScope>,
+
+    // Modules ordered so that every module comes after the modules it imports
+    pub order: Vec<FileId>,
+}
+
+impl Resolution {
+    /// Items `name` may refer to inside `module`. Items of the module itself
+    /// shadow imported ones; more than one result means the name is ambiguous.
+    /// Only `pub` items of imported modules are visible.
+    pub fn lookup(&self, module: FileId, name: &str) -> Vec<&Item> {
+        let Some(scope) = self.scopes.get(&module) else {
+            return Vec::new();
+        };
+
+        if let Some(item) = scope.items.get(name) {
+            return vec![item];
+        }
+
+        self.imported(scope, name)
+            .into_iter()
+            .filter(|item| item.vis == Visibility::Public)
+            .collect()
+    }
+
+    /// Private items called `name` in the modules imported by `module`. These
+    /// are what `name` would refer to if they were `pub`.
+    pub fn lookup_private(&self, module: FileId, name: &str) -> Vec<&Item> {
+        let Some(scope) = self.scopes.get(&module) else {
+            return Vec::new();
+        };
+
+        self.imported(scope, name)
+            .into_iter()
+            .filter(|item| item.vis == Visibility::Private)
+            .collect()
+    }
+
+    /// Public items called `name` in any module, imported or not.
+    pub fn defined_anywhere(&self, name: &str) -> Vec<&Item> {
+        self.scopes
+            .values()
+            .filter_map(|scope| scope.items.get(name))
+            .filter(|item| item.vis == Visibility::Public)
+            .collect()
+    }
+
+    fn imported(&self, scope: &ModuleScope, name: &str) -> Vec<&Item> {
+        scope
+            .imports
+            .iter()
+            .filter_map(|import| self.scopes.get(import)?.items.get(name))
+            .collect()
+    }
+}
+
+/// Collect the items of every module, resolve imports against the modules
+/// known to `module_manager` and order modules by their dependencies.
+pub fn resolve_program(ast: &Ast, module_manager: &ModuleManager) -> (Resolution, Vec<Diagnostic>) {
+    let mut resolution = Resolution::default();
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    let mut graph = ModuleGraph::new();
+
+    // Span of the import behind every edge of the graph, to report cycles with
+    let mut import_spans: BTreeMap<(FileId, FileId), Span> = BTreeMap::new();
+
+    for module in &ast.program.modules {
+        let module_name = module_manager.get(module.id).name.clone();
+        let mut scope = ModuleScope::default();
+        graph.add_module(module.id);
+
+        for declaration in &module.declarations {
+            let name = &declaration.sig.name;
+            let kind = match declaration.sig.ty {
+                TypeExpr::Struct => ItemKind::Struct,
+                TypeExpr::Enum => ItemKind::Enum,
+                _ => ItemKind::Proc,
+            };
+
+            if let Some(previous) = scope.items.get(&name.content) {
+                diagnostics.push(
+                    Diagnostic::error(format!("`{}` is defined multiple times", name.content))
+                        .with_code(codes::DUPLICATE_DEFINITION)
+                        .with_primary(name.span, "redefined here")
+                        .with_secondary(previous.span, "first defined here"),
+                );
+                continue;
+            }
+
+            scope.items.insert(
+                name.content.clone(),
+                Item {
+                    vis: declaration.vis,
+                    name: name.content.clone(),
+                    kind,
+                    module: module.id,
+                    module_name: module_name.clone(),
+                    span: name.span,
+                },
+            );
+        }
+
+        for import in &module.imports {
+            let import_name = import.name();
+            let Some(imported) = module_manager.find_by_name(&import_name) else {
+                diagnostics.push(
+                    Diagnostic::error(format!("unresolved import `{import_name}`"))
+                        .with_code(codes::UNRESOLVED_IMPORT)
+                        .with_primary(import.path_span(), "no such module")
+                        .with_note(format!(
+                            "expected the module at `{}`",
+                            module_manager.path_for_name(&import_name).display()
+                        )),
+                );
+                continue;
+            };
+
+            if !scope.imports.contains(&imported.id) {
+                scope.imports.push(imported.id);
+            }
+            graph.add_dependency(module.id, imported.id);
+            import_spans
+                .entry((module.id, imported.id))
+                .or_insert(import.span);
+        }
+
+        resolution.scopes.insert(module.id, scope);
+    }
+
+    resolution.order = match graph.topological_order() {
+        Ok(order) => order,
+        Err(cycles) => {
+            for cycle in cycles {
+                diagnostics.push(import_cycle(&cycle, &import_spans, module_manager));
+            }
+
+            // Any order will do for checking the remaining modules
+            ast.program.modules.iter().map(|module| module.id).collect()
+        }
+    };
+
+    (resolution, diagnostics)
+}
+
+// `cycle` starts and ends with the same module (e.g. `[a, b, a]`)
+fn import_cycle(
+    cycle: &[FileId],
+    import_spans: &BTreeMap<(FileId, FileId), Span>,
+    module_manager: &ModuleManager,
+) -> Diagnostic {
+    let names = cycle
+        .iter()
+        .map(|&module| format!("`{}`", module_manager.get(module).name))
+        .collect::<Vec<String>>();
+
+    // The import closing the cycle is the problem, the others explain it
+    let edges = cycle.windows(2).collect::<Vec<&[FileId]>>();
+    let (closing_edge, other_edges) = edges.split_last().expect("Cycles have an edge");
+
+    let mut diagnostic = Diagnostic::error(format!("import cycle: {}", names.join(" -> ")))
+        .with_code(codes::IMPORT_CYCLE)
+        .with_primary(
+            import_spans[&(closing_edge[0], closing_edge[1])],
+            "this import closes the cycle",
+        );
+
+    for edge in other_edges {
+        diagnostic = diagnostic.with_secondary(
+            import_spans[&(edge[0], edge[1])],
+            format!("`{}` is imported here", module_manager.get(edge[1]).name),
+        );
+    }
+
+    diagnostic.with_help("move the shared declarations into a module both can import")
+}
+
+pub(crate) fn private_item(token: &Token, item: &Item) -> Diagnostic {
+    // Declarations without `pub` start with their name
+    let decl_start = Span::new(item.span.file, item.span.start, item.span.start);
+
+    Diagnostic::error(format!(
+        "`{}` is private to module `{}`",
+        token.content, item.module_name
+    ))
+    .with_code(codes::PRIVATE_ITEM)
+    .with_primary(token.span, "private item")
+    .with_secondary(item.span, "declared here without `pub`")
+    .with_suggestion(decl_start, "make it public", "pub ")
+}
+
+pub(crate) fn ambiguous_name(token: &Token, items: &[&Item]) -> Diagnostic {
+    let mut diagnostic = Diagnostic::error(format!("`{}` is ambiguous", token.content))
+        .with_code(codes::AMBIGUOUS_NAME)
+        .with_primary(token.span, "ambiguous name");
+
+    for item in items {
+        diagnostic = diagnostic.with_secondary(
+            item.span,
+            format!("defined in module `{}`", item.module_name),
+        );
+    }
+
+    diagnostic.with_help("remove all but one of the imports defining it")
+}
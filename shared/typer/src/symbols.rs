@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+use diagnostics::{Diagnostic, codes};
+use lexical_analyzer::Token;
+use parser::{
+    Ast, Atom, Block, DeclarationDef, Expression, FunctionDef, Mutability, Param, Statement,
+    TypeExpr,
+};
+use span::{FileId, Span};
+
+use crate::resolve::{self, ItemKind, Resolution};
+
+/// Index of a declaration in the `SymbolTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeclId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclKind {
+    // Top-level struct, enum or proc
+    Item(ItemKind),
+
+    // Parameter of a proc
+    Param,
+
+    // `name := ...` or `name :: type = ...` inside a proc body
+    Local(Mutability),
+
+    // Variable counting through a `for` loop
+    LoopVar,
+}
+
+/// Anything an identifier can refer to.
+#[derive(Debug, Clone)]
+pub struct Decl {
+    pub name: String,
+    pub kind: DeclKind,
+    pub module: FileId,
+
+    // Span of the declared name
+    pub span: Span,
+}
+
+/// Every declaration of the program and the declaration each identifier refers to.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    pub decls: Vec<Decl>,
+
+    // Keyed by the span of the identifier. Declared names map to their own declaration.
+    pub ids: HashMap<Span, DeclId>,
+
+    // Top-level items by defining module and name
+    items: HashMap<(FileId, String), DeclId>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, id: DeclId) -> &Decl {
+        &self.decls[id.0]
+    }
+
+    /// Declaration `token` refers to, if it was resolved.
+    pub fn resolve(&self, token: &Token) -> Option<DeclId> {
+        self.ids.get(&token.span).copied()
+    }
+
+    /// Declaration of the item `name` defined in `module`.
+    pub fn item(&self, module: FileId, name: &str) -> Option<DeclId> {
+        self.items.get(&(module, name.to_string())).copied()
+    }
+
+    fn declare(&mut self, decl: Decl) -> DeclId {
+        let id = DeclId(self.decls.len());
+        self.ids.insert(decl.span, id);
+        self.decls.push(decl);
+        id
+    }
+}
+
+/// Resolve every identifier used in proc bodies and types to its declaration.
+/// Locals are looked up from the innermost block outwards, then in the module's
+/// scope. Undefined and duplicate names are errors; shadowing a local is a warning.
+pub fn resolve_names(ast: &Ast, resolution: &Resolution) -> (SymbolTable, Vec<Diagnostic>) {
+    let mut table = SymbolTable::default();
+    for scope in resolution.scopes.values() {
+        for item in scope.items.values() {
+            let id = table.declare(Decl {
+                name: item.name.clone(),
+                kind: DeclKind::Item(item.kind),
+                module: item.module,
+                span: item.span,
+            });
+            table.items.insert((item.module, item.name.clone()), id);
+        }
+    }
+
+    let mut resolver = NameResolver {
+        module: FileId::default(),
+        resolution,
+        table,
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+
+    for module in &ast.program.modules {
+        resolver.module = module.id;
+        for declaration in &module.declarations {
+            resolver.resolve_type(&declaration.sig.ty);
+            match (&declaration.def, &declaration.sig.ty) {
+                (DeclarationDef::Function { def }, TypeExpr::Proc { params, .. }) => {
+                    resolver.resolve_function(params, def);
+                }
+                (DeclarationDef::Struct { fields }, _) => {
+                    for field in fields {
+                        resolver.resolve_type(&field.ty);
+                    }
+                    check_duplicates(
+                        fields.iter().map(|field| &field.name),
+                        "field",
+                        &mut resolver.diagnostics,
+                    );
+                }
+                (DeclarationDef::Enum { variants }, _) => {
+                    check_duplicates(
+                        variants.iter().map(|variant| &variant.name),
+                        "variant",
+                        &mut resolver.diagnostics,
+                    );
+                }
+                _ => (),
+            }
+        }
+    }
+
+    (resolver.table, resolver.diagnostics)
+}
+
+// Report names declared more than once in the same struct or enum
+fn check_duplicates<'a>(
+    names: impl Iterator<Item = &'a Token>,
+    what: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut seen: HashMap<&str, Span> = HashMap::new();
+    for name in names {
+        if let Some(&previous) = seen.get(name.content.as_str()) {
+            diagnostics.push(duplicate_definition(name, previous, what));
+            continue;
+        }
+        seen.insert(&name.content, name.span);
+    }
+}
+
+fn duplicate_definition(name: &Token, previous: Span, what: &str) -> Diagnostic {
+    Diagnostic::error(format!(
+        "{what} `{}` is defined multiple times",
+        name.content
+    ))
+    .with_code(codes::DUPLICATE_DEFINITION)
+    .with_primary(name.span, "redefined here")
+    .with_secondary(previous, "first defined here")
+}
+
+struct NameResolver<'a> {
+    module: FileId,
+    resolution: &'a Resolution,
+    table: SymbolTable,
+
+    // Proc scope followed by its block scopes, innermost last. The module
+    // scope is looked up in `resolution`.
+    scopes: Vec<Vec<DeclId>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl NameResolver<'_> {
+    fn resolve_function(&mut self, params: &[Param], def: &FunctionDef) {
+        self.scopes.push(Vec::new());
+        for param in params {
+            let duplicate = self.scopes[0]
+                .iter()
+                .map(|&id| self.table.get(id))
+                .find(|decl| decl.name == param.name.content)
+                .map(|decl| decl.span);
+            if let Some(previous) = duplicate {
+                self.diagnostics
+                    .push(duplicate_definition(&param.name, previous, "parameter"));
+                continue;
+            }
+
+            self.declare_local(&param.name, DeclKind::Param);
+        }
+
+        for statement in &def.body {
+            self.resolve_statement(statement);
+        }
+        if let Some(expr) = &def.expr {
+            self.resolve_expr(expr);
+        }
+
+        self.scopes.pop();
+    }
+
+    fn resolve_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Binding {
+                mutability,
+                name,
+                ty,
+                value,
+            } => {
+                if let Some(ty) = ty {
+                    self.resolve_type(ty);
+                }
+
+                // Declared after its initializer, so `x := x + 1` refers to an earlier `x`
+                self.resolve_expr(value);
+                self.declare_local(name, DeclKind::Local(*mutability));
+            }
+            Statement::Assign { target, value }
+            | Statement::CompoundAssign { target, value, .. } => {
+                self.resolve_expr(target);
+                self.resolve_expr(value);
+            }
+            Statement::Expr(expr) => self.resolve_expr(expr),
+            Statement::Return { value, .. } | Statement::Break { value, .. } => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+            Statement::While { cond, body, .. } => {
+                self.resolve_expr(cond);
+                self.resolve_block(body);
+            }
+            Statement::For {
+                var,
+                start,
+                end,
+                body,
+                ..
+            } => {
+                self.resolve_expr(start);
+                self.resolve_expr(end);
+
+                self.scopes.push(Vec::new());
+                self.declare_local(var, DeclKind::LoopVar);
+                self.resolve_block(body);
+                self.scopes.pop();
+            }
+            Statement::Continue { .. } => (),
+        }
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        for statement in &block.body {
+            self.resolve_statement(statement);
+        }
+        if let Some(expr) = &block.expr {
+            self.resolve_expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    fn resolve_expr(&mut self, expr: &Expression) {
+        match expr {
+            Expression::AddExpr(lhs, rhs)
+            | Expression::SubExpr(lhs, rhs)
+            | Expression::MulExpr(lhs, rhs)
+            | Expression::DivExpr(lhs, rhs)
+            | Expression::ModExpr(lhs, rhs)
+            | Expression::LtExpr(lhs, rhs)
+            | Expression::LteExpr(lhs, rhs)
+            | Expression::GtExpr(lhs, rhs)
+            | Expression::GteExpr(lhs, rhs)
+            | Expression::EqExpr(lhs, rhs)
+            | Expression::NeExpr(lhs, rhs)
+            | Expression::AndExpr(lhs, rhs)
+            | Expression::OrExpr(lhs, rhs)
+            | Expression::BitAndExpr(lhs, rhs)
+            | Expression::BitOrExpr(lhs, rhs)
+            | Expression::BitXorExpr(lhs, rhs)
+            | Expression::ShlExpr(lhs, rhs)
+            | Expression::ShrExpr(lhs, rhs)
+            | Expression::IndexExpr(lhs, rhs, _) => {
+                self.resolve_expr(lhs);
+                self.resolve_expr(rhs);
+            }
+            Expression::NegExpr(inner, _)
+            | Expression::NotExpr(inner, _)
+            | Expression::BitNotExpr(inner, _)
+            | Expression::ParenExpr(inner, _) => self.resolve_expr(inner),
+            Expression::CastExpr { expr, ty, .. } => {
+                self.resolve_expr(expr);
+                self.resolve_type(ty);
+            }
+            Expression::CallExpr(callee, args, _) => {
+                self.resolve_expr(callee);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+
+            // Fields are looked up in the type of the base by the type checker
+            Expression::FieldExpr(base, _) => self.resolve_expr(base),
+            Expression::IfExpr {
+                cond,
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                self.resolve_expr(cond);
+                self.resolve_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_expr(else_branch);
+                }
+            }
+            Expression::BlockExpr(block) | Expression::LoopExpr { body: block, .. } => {
+                self.resolve_block(block);
+            }
+            Expression::Atom {
+                inner: Atom::Identifier(token),
+            } => self.resolve_use(token),
+            Expression::Atom {
+                inner: Atom::Literal(_),
+            } => (),
+        }
+    }
+
+    // Types name items only, never locals. Unresolved types are reported when
+    // the type is evaluated, so they are skipped here.
+    fn resolve_type(&mut self, ty: &TypeExpr) {
+        match ty {
+            TypeExpr::Named(token) => {
+                if let [item] = self
+                    .resolution
+                    .lookup(self.module, &token.content)
+                    .as_slice()
+                {
+                    let id = self
+                        .table
+                        .item(item.module, &item.name)
+                        .expect("Every item is declared");
+                    self.table.ids.insert(token.span, id);
+                }
+            }
+            TypeExpr::Proc { params, output } => {
+                for param in params {
+                    self.resolve_type(&param.ty);
+                }
+                if let Some(output) = output {
+                    self.resolve_type(output);
+                }
+            }
+            TypeExpr::Prim(_) | TypeExpr::Struct | TypeExpr::Enum => (),
+        }
+    }
+
+    fn declare_local(&mut self, name: &Token, kind: DeclKind) {
+        // Later bindings of a name hide earlier ones for the rest of the scope
+        if let Some(shadowed) = self.lookup_local(&name.content) {
+            let shadowed = self.table.get(shadowed);
+            self.diagnostics.push(
+                Diagnostic::warning(format!("`{}` shadows an earlier binding", name.content))
+                    .with_code(codes::SHADOWED_NAME)
+                    .with_primary(name.span, "shadowing binding")
+                    .with_secondary(shadowed.span, "previously bound here")
+                    .with_help("rename the binding if the shadowing is unintended"),
+            );
+        }
+
+        let id = self.table.declare(Decl {
+            name: name.content.clone(),
+            kind,
+            module: self.module,
+            span: name.span,
+        });
+        self.scopes
+            .last_mut()
+            .expect("Locals are declared inside a proc")
+            .push(id);
+    }
+
+    // Innermost local called `name`
+    fn lookup_local(&self, name: &str) -> Option<DeclId> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .copied()
+            .find(|&id| self.table.get(id).name == name)
+    }
+
+    fn resolve_use(&mut self, token: &Token) {
+        let name = token.content.as_str();
+        if let Some(id) = self.lookup_local(name) {
+            self.table.ids.insert(token.span, id);
+            return;
+        }
+
+        match self.resolution.lookup(self.module, name).as_slice() {
+            [item] => {
+                let id = self
+                    .table
+                    .item(item.module, &item.name)
+                    .expect("Every item is declared");
+                self.table.ids.insert(token.span, id);
+            }
+            [] => {
+                if let Some(item) = self.resolution.lookup_private(self.module, name).first() {
+                    self.diagnostics.push(resolve::private_item(token, item));
+                    return;
+                }
+
+                let mut diagnostic =
+                    Diagnostic::error(format!("cannot find `{name}` in this scope"))
+                        .with_code(codes::UNRESOLVED_NAME)
+                        .with_primary(token.span, "not found in this scope");
+                for item in self.resolution.defined_anywhere(name) {
+                    diagnostic = diagnostic.with_help(format!(
+                        "`{name}` is defined in module `{}`; add `import {};`",
+                        item.module_name, item.module_name
+                    ));
+                }
+                self.diagnostics.push(diagnostic);
+            }
+            items => self.diagnostics.push(resolve::ambiguous_name(token, items)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use span::Span;
+
+    use super::*;
+    use crate::{resolve::resolve_program, tests::parse};
+
+    #[test]
+    fn type_names_resolve_to_their_items() {
+        let src = "Point :: struct { x :: s32, color :: Color }
+            Color :: enum { Red }
+            make :: proc (p :: Point) -> Point {
+                q :: Point = p;
+                n := q.x as s64;
+                q
+            }";
+        let (module_manager, ast) = parse(src);
+        let (resolution, diagnostics) = resolve_program(&ast, &module_manager);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let (symbols, diagnostics) = resolve_names(&ast, &resolution);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        let module = ast.program.modules[0].id;
+        for name in ["Point", "Color"] {
+            let item = symbols.item(module, name).expect("Items are declared");
+            let uses = src.match_indices(name).collect::<Vec<(usize, &str)>>();
+            assert_eq!(uses.len(), if name == "Point" { 4 } else { 2 });
+            for (start, _) in uses {
+                let span = Span::new(module, start, start + name.len());
+                assert_eq!(symbols.ids.get(&span), Some(&item), "`{name}` at {start}");
+            }
+        }
+    }
+}
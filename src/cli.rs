@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: gemstone [OPTIONS] [FILES]...
+
+Compiles the given `.gem` files, or every module below the module root if none are given.
+
+Options:
+    --root <DIR>               Directory to load modules from (default: `modules/`)
+    --message-format <FORMAT>  How to report diagnostics: `human` (default) or `json`
+    -h, --help                 Print this message";
+
+/// How diagnostics are reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    // Rendered source snippets on stderr
+    Human,
+
+    // One JSON object per line on stdout
+    Json,
+}
+
+impl TryFrom<&str> for MessageFormat {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            _ => Err(format!(
+                "invalid message format `{value}`, expected `human` or `json`"
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub message_format: MessageFormat,
+    pub root: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub help: bool,
+}
+
+impl Options {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            message_format: MessageFormat::Human,
+            root: PathBuf::from("modules/"),
+            files: Vec::new(),
+            help: false,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--message-format" => {
+                    let value = args
+                        .next()
+                        .ok_or("`--message-format` expects a value".to_string())?;
+                    options.message_format = MessageFormat::try_from(value.as_str())?;
+                }
+                "--root" => {
+                    let value = args.next().ok_or("`--root` expects a value".to_string())?;
+                    options.root = PathBuf::from(value);
+                }
+                _ => {
+                    if let Some(value) = arg.strip_prefix("--message-format=") {
+                        options.message_format = MessageFormat::try_from(value)?;
+                    } else if let Some(value) = arg.strip_prefix("--root=") {
+                        options.root = PathBuf::from(value);
+                    } else if arg.starts_with('-') {
+                        return Err(format!("unexpected argument `{arg}`"));
+                    } else {
+                        options.files.push(PathBuf::from(arg));
+                    }
+                }
+            }
+        }
+
+        Ok(options)
+    }
+}
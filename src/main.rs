@@ -1,26 +1,117 @@
+mod cli;
+
+use std::io::IsTerminal;
+
+use cli::{MessageFormat, Options, USAGE};
+use diagnostics::{Diagnostic, Renderer};
 use lexical_analyzer::Scanner;
-use module_manager::ModuleManager;
-use parser;
+use module_manager::{DiskFileSystem, ModuleManager};
 use preprocessor::Preprocessor;
-
-use log;
-use pretty_env_logger;
+use span::{SourceMap, Span};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Register logging system
     pretty_env_logger::init();
 
-    // Get modules
-    let mut module_manager = ModuleManager::new()?;
+    let options = match Options::from_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    let format = options.message_format;
+
+    // Get modules, either the files given on the command line or everything below the root
+    let module_manager = match options.files.is_empty() {
+        true => ModuleManager::from_root(&options.root),
+        false => ModuleManager::from_files(options.files).map(|mm| mm.with_root(&options.root)),
+    };
+    let mut module_manager = match module_manager {
+        Ok(module_manager) => module_manager,
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    // Imported modules that were not loaded yet (e.g. when compiling explicit
+    // files) are loaded from the module root, then everything is parsed again
+    let (ast, diagnostics) = loop {
+        let (ast, diagnostics) = parse_modules(&mut module_manager);
+
+        let mut loaded_imports = false;
+        for import in ast
+            .program
+            .modules
+            .iter()
+            .flat_map(|module| &module.imports)
+        {
+            let import_name = import.name();
+            if module_manager.find_by_name(&import_name).is_some() {
+                continue;
+            }
+
+            match module_manager.load_by_name(&DiskFileSystem, &import_name) {
+                Ok(loaded) => loaded_imports |= loaded.is_some(),
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        if !loaded_imports {
+            break (ast, diagnostics);
+        }
+    };
+    exit_on_errors(&diagnostics, module_manager.source_map(), format);
+
+    // Resolve imports across modules and the types named in declarations
+    let diagnostics = typer::check(&ast, &module_manager);
+    exit_on_errors(&diagnostics, module_manager.source_map(), format);
 
-    // Preprocess modules
-    let mut preprocessor = Preprocessor::new(&mut module_manager);
-    preprocessor.detect_nonvalid_chars();
+    Ok(())
+}
+
+/// Preprocess, scan and parse every module. Invalid characters, lexical and
+/// syntax errors are recovered from and returned so that all of them can be
+/// reported at once.
+fn parse_modules(module_manager: &mut ModuleManager) -> (parser::Ast, Vec<Diagnostic>) {
+    // Preprocess modules. Comments are stripped first so that they may
+    // contain characters which are not valid in gemstone source.
+    let mut preprocessor = Preprocessor::new(module_manager);
     preprocessor.strip_comments();
+    let mut diagnostics = preprocessor.detect_nonvalid_chars();
 
-    // Get the token stream for each module
-    let scanner = Scanner::new(&mut module_manager);
-    let scanner_data_from_modules = scanner.scan()?;
+    // Get the token stream for each module. Invalid characters and lexical
+    // errors are reported together with the syntax errors found in the
+    // remaining tokens.
+    let scanner = Scanner::new(module_manager);
+    let (scanner_data_from_modules, scanner_errors) = scanner.scan();
+
+    // The scanner rejects invalid characters as well; report them only once
+    let invalid_chars = diagnostics
+        .iter()
+        .filter_map(Diagnostic::primary_span)
+        .collect::<Vec<Span>>();
+    diagnostics.extend(
+        scanner_errors
+            .into_iter()
+            .map(Diagnostic::from)
+            .filter(|diagnostic| {
+                !diagnostic.primary_span().is_some_and(|span| {
+                    invalid_chars.iter().any(|chr| {
+                        span.file == chr.file && span.start <= chr.start && chr.end <= span.end
+                    })
+                })
+            }),
+    );
 
     for module_scanner_data in &scanner_data_from_modules {
         let tokens = &module_scanner_data.tokens;
@@ -32,7 +123,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         log::info!("{tokens:#?} tokens");
     }
 
-    let ast = parser::Ast::new(scanner_data_from_modules);
+    let (ast, parser_diagnostics) = parser::Ast::new(scanner_data_from_modules);
+    diagnostics.extend(parser_diagnostics);
 
-    Ok(())
+    (ast, diagnostics)
+}
+
+/// Report `diagnostics` in the requested `format` and stop compilation if any of them is an error.
+fn exit_on_errors(diagnostics: &[Diagnostic], source_map: &SourceMap, format: MessageFormat) {
+    match format {
+        MessageFormat::Human => {
+            let renderer = Renderer::new(source_map, std::io::stderr().is_terminal());
+            for diagnostic in diagnostics {
+                eprintln!("{}", renderer.render(diagnostic));
+            }
+        }
+        MessageFormat::Json => {
+            for diagnostic in diagnostics {
+                println!("{}", diagnostics::to_json_line(diagnostic, source_map));
+            }
+        }
+    }
+
+    let error_count = diagnostics.iter().filter(|d| d.is_error()).count();
+    if error_count > 0 {
+        if format == MessageFormat::Human {
+            eprintln!("error: could not compile due to {error_count} previous error(s)");
+        }
+        std::process::exit(1);
+    }
 }